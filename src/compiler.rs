@@ -4,6 +4,7 @@ use crate::{
     obj::{Function, FunctionUpvalue, LoxString},
     op_code::{LocalIndex, UpvalueIndex},
     scanner::Token,
+    value::Value,
 };
 
 #[derive(Clone, Copy)]
@@ -12,6 +13,12 @@ pub enum FunctionType {
     Function,
     Method,
     Initializer,
+    /// A property read, declared without a parameter list
+    Getter,
+    /// A property write, whose assignment evaluates to its parameter
+    Setter,
+    /// A method declared with `class` in a class body, called with the class as `this`
+    StaticMethod,
 }
 
 /// A compiler for a function, including the implicit top-level function, <script>
@@ -24,6 +31,9 @@ pub struct Compiler<'source> {
     locals: Vec<Local<'source>>,
     /// The number of blocks surrounding the current bit of code
     scope_depth: u32,
+    /// Enums declared in this function's blocks, for checking that matches on them cover every
+    /// variant
+    enums: Vec<ScopedEnum<'source>>,
 }
 
 impl<'source> Compiler<'source> {
@@ -40,6 +50,7 @@ impl<'source> Compiler<'source> {
             name,
             depth: Some(0),
             is_captured: false,
+            is_const: false,
         });
 
         Self {
@@ -48,6 +59,7 @@ impl<'source> Compiler<'source> {
             function: Function::new(function_name),
             function_type,
             scope_depth: 0,
+            enums: vec![],
         }
     }
 
@@ -57,6 +69,8 @@ impl<'source> Compiler<'source> {
 
     pub fn end_scope(&mut self) {
         self.scope_depth -= 1;
+        let depth = self.scope_depth;
+        self.enums.retain(|scoped| scoped.depth <= depth);
     }
 
     pub fn add_local(&mut self, name: Token<'source>) -> Result<()> {
@@ -66,11 +80,13 @@ impl<'source> Compiler<'source> {
             ));
         }
 
+        self.hide_enum(name.lexeme);
         // Only "declare" for now, by assigning sentinel value
         self.locals.push(Local {
             name,
             depth: None,
             is_captured: false,
+            is_const: false,
         });
 
         Ok(())
@@ -107,17 +123,28 @@ impl<'source> Compiler<'source> {
         self.locals.last_mut().unwrap().depth = Some(self.scope_depth);
     }
 
+    pub fn mark_var_const(&mut self) {
+        if !self.is_local_scope() {
+            return;
+        }
+
+        self.locals.last_mut().unwrap().is_const = true;
+    }
+
     pub fn remove_local(&mut self) {
         self.locals.pop();
     }
 
-    pub fn resolve_local(&mut self, name: Token) -> Result<Option<LocalIndex>> {
+    /// `assigning` is whether the variable is being assigned rather than read
+    pub fn resolve_local(&mut self, name: Token, assigning: bool) -> Result<Option<LocalIndex>> {
         for (i, local) in self.locals.iter().enumerate().rev() {
             if name.lexeme == local.name.lexeme {
                 return if local.depth.is_none() {
                     Err(LoxError::CompileError(
                         "Can't read local variable in its own initializer.",
                     ))
+                } else if assigning && local.is_const {
+                    Err(LoxError::CompileError("Can't assign to a constant."))
                 } else {
                     Ok(Some(i as u8))
                 };
@@ -126,13 +153,17 @@ impl<'source> Compiler<'source> {
         Ok(None)
     }
 
-    pub fn resolve_upvalue(&mut self, name: Token) -> Result<Option<UpvalueIndex>> {
+    pub fn resolve_upvalue(
+        &mut self,
+        name: Token,
+        assigning: bool,
+    ) -> Result<Option<UpvalueIndex>> {
         Ok(if let Some(enclosing) = self.enclosing.as_mut() {
-            if let Some(index) = enclosing.resolve_local(name)? {
+            if let Some(index) = enclosing.resolve_local(name, assigning)? {
                 enclosing.locals[index as usize].is_captured = true;
                 Some(self.add_upvalue(index, true)?)
-            } else if let Some(upvalue) = enclosing.resolve_upvalue(name)? {
-                Some(self.add_upvalue(upvalue as u8, false)?)
+            } else if let Some(upvalue) = enclosing.resolve_upvalue(name, assigning)? {
+                Some(self.add_upvalue(upvalue, false)?)
             } else {
                 None
             }
@@ -141,12 +172,48 @@ impl<'source> Compiler<'source> {
         })
     }
 
+    pub fn declare_enum(&mut self, name: &'source str, variants: Vec<&'source str>) {
+        self.enums.push(ScopedEnum {
+            name,
+            variants: Some(variants),
+            depth: self.scope_depth,
+        });
+    }
+
+    /// A variable declared with the same name as an enum shadows it, until the end of its block
+    pub fn hide_enum(&mut self, name: &'source str) {
+        if self.resolve_enum(name).is_some() {
+            self.enums.push(ScopedEnum {
+                name,
+                variants: None,
+                depth: self.scope_depth,
+            });
+        }
+    }
+
+    /// The variants of the enum a name refers to, found through enclosing blocks and functions the
+    /// way variables are
+    pub fn resolve_enum(&self, name: &str) -> Option<&[&'source str]> {
+        match self.enums.iter().rev().find(|scoped| scoped.name == name) {
+            Some(scoped) => scoped.variants.as_deref(),
+            None => self
+                .enclosing
+                .as_ref()
+                .and_then(|enclosing| enclosing.resolve_enum(name)),
+        }
+    }
+
     /// Is the current scope a non-global scope?
     pub fn is_local_scope(&self) -> bool {
         self.scope_depth > 0
     }
 
-    pub fn get_local(&self) -> &Local {
+    /// The stack slot of the most recently declared local
+    pub fn last_local_slot(&self) -> LocalIndex {
+        (self.locals.len() - 1) as LocalIndex
+    }
+
+    pub fn get_local(&self) -> &Local<'_> {
         self.locals.last().unwrap()
     }
 
@@ -159,6 +226,16 @@ impl<'source> Compiler<'source> {
         }
     }
 
+    /// Whether each local in the current scope is captured by a closure, innermost first
+    pub fn scope_local_captures(&self) -> Vec<bool> {
+        self.locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_some_and(|depth| depth >= self.scope_depth))
+            .map(|local| local.is_captured)
+            .collect()
+    }
+
     pub fn is_local_already_in_scope(&self, name: Token) -> bool {
         // Search for a variable with the same name in the current scope
         for local in self.locals.iter().rev() {
@@ -179,6 +256,10 @@ impl<'source> Compiler<'source> {
 pub struct ClassCompiler {
     pub enclosing: Option<Box<ClassCompiler>>,
     pub has_superclass: bool,
+    /// Whether this is a trait, whose methods find their superclass through the class including them
+    pub is_trait: bool,
+    /// Whether a `class var` initializer is being compiled, where `this` is the class itself
+    pub in_static_field: bool,
 }
 
 impl ClassCompiler {
@@ -186,6 +267,8 @@ impl ClassCompiler {
         Self {
             enclosing,
             has_superclass: false,
+            is_trait: false,
+            in_static_field: false,
         }
     }
 }
@@ -196,10 +279,52 @@ pub struct Local<'source> {
     /// None means declared but not defined
     depth: Option<u32>,
     pub is_captured: bool,
+    /// Declared with `const`, so it can't be assigned after its initializer
+    is_const: bool,
+}
+
+/// An enum's name and variants as seen from the block it was declared in
+struct ScopedEnum<'source> {
+    name: &'source str,
+    /// None when a variable declared later shadows the enum
+    variants: Option<Vec<&'source str>>,
+    depth: u32,
 }
 
-#[derive(Debug)]
-pub struct Upvalue {
-    pub index: u8,
-    pub is_local: bool,
+/// The left-hand side of a destructuring declaration or assignment
+pub enum Destructure<'source> {
+    /// `(a, b)` takes the items of a list in order
+    Tuple(Vec<Token<'source>>),
+    /// `{x, y: b}` takes fields of an instance, each into a variable named after the field unless
+    /// another name is given
+    Fields(Vec<(Token<'source>, Token<'source>)>),
+}
+
+/// A pattern in a `case` of a match statement
+pub enum Pattern<'source> {
+    /// `_` matches anything
+    Wildcard,
+    /// A name matches anything, binding the value to a new local
+    Binding(Token<'source>),
+    /// A literal matches an equal value
+    Literal(Value),
+    /// A dotted name like `Color.Red` matches an equal value
+    Constant(Vec<Token<'source>>),
+    /// `Point { x, y: 0 }` matches instances of the class whose fields match the subpatterns
+    Instance {
+        class: Token<'source>,
+        fields: Vec<(Token<'source>, Pattern<'source>)>,
+    },
+}
+
+impl Pattern<'_> {
+    pub fn has_bindings(&self) -> bool {
+        match self {
+            Pattern::Binding(_) => true,
+            Pattern::Instance { fields, .. } => {
+                fields.iter().any(|(_, pattern)| pattern.has_bindings())
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Constant(_) => false,
+        }
+    }
 }
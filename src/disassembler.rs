@@ -1,6 +1,6 @@
 use crate::{
     chunk::Chunk,
-    op_code::{Constant, Invoke, Jump, LocalIndex, OpCode},
+    op_code::{Constant, Invoke, Jump, OpCode},
 };
 
 #[cfg(feature = "debug_print_code")]
@@ -36,6 +36,15 @@ pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
         OpCode::Subtract => simple_instruction("OP_SUBTRACT", offset),
         OpCode::Multiply => simple_instruction("OP_MULTIPLY", offset),
         OpCode::Divide => simple_instruction("OP_DIVIDE", offset),
+        OpCode::IntDivide => simple_instruction("OP_INT_DIVIDE", offset),
+        OpCode::Modulo => simple_instruction("OP_MODULO", offset),
+        OpCode::Power => simple_instruction("OP_POWER", offset),
+        OpCode::BitAnd => simple_instruction("OP_BIT_AND", offset),
+        OpCode::BitOr => simple_instruction("OP_BIT_OR", offset),
+        OpCode::BitXor => simple_instruction("OP_BIT_XOR", offset),
+        OpCode::BitNot => simple_instruction("OP_BIT_NOT", offset),
+        OpCode::ShiftLeft => simple_instruction("OP_SHIFT_LEFT", offset),
+        OpCode::ShiftRight => simple_instruction("OP_SHIFT_RIGHT", offset),
         OpCode::Nil => simple_instruction("OP_NIL", offset),
         OpCode::True => simple_instruction("OP_TRUE", offset),
         OpCode::False => simple_instruction("OP_FALSE", offset),
@@ -45,18 +54,25 @@ pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
         OpCode::Less => simple_instruction("OP_LESS", offset),
         OpCode::Print => simple_instruction("OP_PRINT", offset),
         OpCode::Pop => simple_instruction("OP_POP", offset),
+        OpCode::Dup => simple_instruction("OP_DUP", offset),
+        OpCode::DupPair => simple_instruction("OP_DUP_PAIR", offset),
         OpCode::DefineGlobal(constant) => {
             constant_instruction("OP_DEFINE_GLOBAL", chunk, offset, constant)
         }
+        OpCode::DefineConstGlobal(constant) => {
+            constant_instruction("OP_DEFINE_CONST_GLOBAL", chunk, offset, constant)
+        }
         OpCode::GetGlobal(constant) => {
             constant_instruction("OP_GET_GLOBAL", chunk, offset, constant)
         }
         OpCode::SetGlobal(constant) => {
             constant_instruction("OP_SET_GLOBAL", chunk, offset, constant)
         }
-        OpCode::GetLocal(LocalIndex { index }) => byte_instruction("OP_GET_LOCAL", offset, index),
-        OpCode::SetLocal(LocalIndex { index }) => byte_instruction("OP_SET_LOCAL", offset, index),
+        OpCode::GetLocal(index) => byte_instruction("OP_GET_LOCAL", offset, index),
+        OpCode::SetLocal(index) => byte_instruction("OP_SET_LOCAL", offset, index),
         OpCode::JumpIfFalse(jump) => jump_instruction("OP_JUMP_IF_FALSE", 1, offset, jump),
+        OpCode::JumpIfNil(jump) => jump_instruction("OP_JUMP_IF_NIL", 1, offset, jump),
+        OpCode::JumpIfNotNil(jump) => jump_instruction("OP_JUMP_IF_NOT_NIL", 1, offset, jump),
         OpCode::Jump(jump) => jump_instruction("OP_JUMP", 1, offset, jump),
         OpCode::Loop(jump) => jump_instruction("OP_LOOP", -1, offset, jump),
         OpCode::Call { arg_count } => byte_instruction("OP_CALL", offset, arg_count),
@@ -64,7 +80,21 @@ pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
         OpCode::GetUpvalue(slot) => byte_instruction("OP_GET_UPVALUE", offset, slot),
         OpCode::SetUpvalue(slot) => byte_instruction("OP_SET_UPVALUE", offset, slot),
         OpCode::CloseUpvalue => simple_instruction("OP_CLOSE_UPVALUE", offset),
+        OpCode::List { item_count } => byte_instruction("OP_LIST", offset, item_count),
+        OpCode::Unpack { item_count } => byte_instruction("OP_UNPACK", offset, item_count),
+        OpCode::GetIndex => simple_instruction("OP_GET_INDEX", offset),
+        OpCode::SetIndex => simple_instruction("OP_SET_INDEX", offset),
         OpCode::Class(constant) => constant_instruction("OP_CLASS", chunk, offset, constant),
+        OpCode::Trait(constant) => constant_instruction("OP_TRAIT", chunk, offset, constant),
+        OpCode::Enum(constant) => constant_instruction("OP_ENUM", chunk, offset, constant),
+        OpCode::Variant { name, field_count } => {
+            println!(
+                "{:-16} {:4} '{}' ({} fields)",
+                "OP_VARIANT", name.slot, chunk.constants[name.slot as usize], field_count,
+            );
+            offset + 1
+        }
+        OpCode::Include { trait_count } => byte_instruction("OP_INCLUDE", offset, trait_count),
         OpCode::GetProperty(constant) => {
             constant_instruction("OP_GET_PROPERTY", chunk, offset, constant)
         }
@@ -72,10 +102,35 @@ pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
             constant_instruction("OP_SET_PROPERTY", chunk, offset, constant)
         }
         OpCode::Method(constant) => constant_instruction("OP_METHOD", chunk, offset, constant),
+        OpCode::Getter(constant) => constant_instruction("OP_GETTER", chunk, offset, constant),
+        OpCode::Setter(constant) => constant_instruction("OP_SETTER", chunk, offset, constant),
+        OpCode::PrivateMethod(constant) => {
+            constant_instruction("OP_PRIVATE_METHOD", chunk, offset, constant)
+        }
+        OpCode::Static(constant) => constant_instruction("OP_STATIC", chunk, offset, constant),
         OpCode::Invoke(invoke) => invoke_instruction("OP_INVOKE", chunk, offset, invoke),
         OpCode::Inherit => simple_instruction("OP_INHERIT", offset),
+        OpCode::IsInstance => simple_instruction("OP_IS_INSTANCE", offset),
+        OpCode::HasField(constant) => constant_instruction("OP_HAS_FIELD", chunk, offset, constant),
+        OpCode::GetPrivate(constant) => {
+            constant_instruction("OP_GET_PRIVATE", chunk, offset, constant)
+        }
+        OpCode::SetPrivate(constant) => {
+            constant_instruction("OP_SET_PRIVATE", chunk, offset, constant)
+        }
+        OpCode::InvokePrivate(invoke) => {
+            invoke_instruction("OP_INVOKE_PRIVATE", chunk, offset, invoke)
+        }
+        OpCode::HomeSuperclass => simple_instruction("OP_HOME_SUPERCLASS", offset),
         OpCode::GetSuper(constant) => constant_instruction("OP_GET_SUPER", chunk, offset, constant),
+        OpCode::SetSuper(constant) => constant_instruction("OP_SET_SUPER", chunk, offset, constant),
         OpCode::SuperInvoke(invoke) => invoke_instruction("OP_SUPER_INVOKE", chunk, offset, invoke),
+        OpCode::GetSuperStatic(constant) => {
+            constant_instruction("OP_GET_SUPER_STATIC", chunk, offset, constant)
+        }
+        OpCode::SuperInvokeStatic(invoke) => {
+            invoke_instruction("OP_SUPER_INVOKE_STATIC", chunk, offset, invoke)
+        }
     }
 }
 
@@ -2,4 +2,10 @@ pub type Result<T> = std::result::Result<T, LoxError>;
 pub enum LoxError {
     CompileError(&'static str),
     RuntimeError,
+    /// A runtime error from an operation the VM's permissions don't allow
+    PermissionDenied,
+    /// The embedder stopped the script, see `Vm::interrupt_handle`
+    Interrupted,
+    /// The script called `exit(code)`
+    Exit(i32),
 }
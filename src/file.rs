@@ -0,0 +1,234 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+};
+
+use crate::{
+    error::Result, gc::GcRef, obj::LoxString, permissions::Permission, value::Value, vm::Vm,
+};
+
+/// The `File` class, with statics for whole-file and directory operations and methods on the
+/// handles returned by `File.open(path, mode)`
+pub fn define_natives(vm: &mut Vm) {
+    let class = vm.define_class("File");
+    vm.define_static_native(class, "read", 1, read);
+    vm.define_static_native(class, "readLines", 1, read_lines);
+    vm.define_static_native(class, "write", 2, write);
+    vm.define_static_native(class, "append", 2, append);
+    vm.define_static_native(class, "exists", 1, exists);
+    vm.define_static_native(class, "remove", 1, remove);
+    vm.define_static_native(class, "listDir", 1, list_dir);
+    vm.define_static_native(class, "mkdir", 1, mkdir);
+    vm.define_static_native(class, "open", 2, open);
+
+    vm.define_method_native(class, "readLine", 0, handle_read_line);
+    vm.define_method_native(class, "read", 0, handle_read);
+    vm.define_method_native(class, "write", 1, handle_write);
+    vm.define_method_native(class, "close", 0, handle_close);
+}
+
+/// The data of a handle, which is None once closed. Dropping the file closes it.
+struct OpenFile(Option<BufReader<File>>);
+
+fn read(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Read)?;
+    match fs::read_to_string(path.as_str()) {
+        Ok(content) => Ok(Value::String(vm.intern(content))),
+        Err(err) => io_error(vm, "read", path, err),
+    }
+}
+
+fn read_lines(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Read)?;
+    match fs::read_to_string(path.as_str()) {
+        Ok(content) => {
+            let lines = content.lines().map(str::to_string).collect();
+            Ok(vm.new_string_list(lines))
+        }
+        Err(err) => io_error(vm, "read", path, err),
+    }
+}
+
+fn write(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Write)?;
+    match fs::write(path.as_str(), args[1].to_string()) {
+        Ok(()) => Ok(Value::Nil),
+        Err(err) => io_error(vm, "write", path, err),
+    }
+}
+
+fn append(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Write)?;
+    let result = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path.as_str())
+        .and_then(|mut file| file.write_all(args[1].to_string().as_bytes()));
+    match result {
+        Ok(()) => Ok(Value::Nil),
+        Err(err) => io_error(vm, "append to", path, err),
+    }
+}
+
+fn exists(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Read)?;
+    Ok(Value::Bool(fs::metadata(path.as_str()).is_ok()))
+}
+
+/// Removes a file or an empty directory
+fn remove(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Write)?;
+    let result = match fs::metadata(path.as_str()) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir(path.as_str()),
+        _ => fs::remove_file(path.as_str()),
+    };
+    match result {
+        Ok(()) => Ok(Value::Nil),
+        Err(err) => io_error(vm, "remove", path, err),
+    }
+}
+
+/// The names of the entries in a directory, sorted
+fn list_dir(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Read)?;
+    let entries = fs::read_dir(path.as_str()).and_then(|entries| {
+        entries
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<std::io::Result<Vec<_>>>()
+    });
+    match entries {
+        Ok(mut names) => {
+            names.sort();
+            Ok(vm.new_string_list(names))
+        }
+        Err(err) => io_error(vm, "list", path, err),
+    }
+}
+
+/// Creates a directory, along with any missing parents
+fn mkdir(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let path = path(vm, args[0], Permission::Write)?;
+    match fs::create_dir_all(path.as_str()) {
+        Ok(()) => Ok(Value::Nil),
+        Err(err) => io_error(vm, "create", path, err),
+    }
+}
+
+/// Opens a handle for reading ("r"), writing ("w") or appending ("a")
+fn open(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let mut options = OpenOptions::new();
+    let permission = match args[1] {
+        Value::String(mode) if mode.as_str() == "r" => {
+            options.read(true);
+            Permission::Read
+        }
+        Value::String(mode) if mode.as_str() == "w" => {
+            options.write(true).create(true).truncate(true);
+            Permission::Write
+        }
+        Value::String(mode) if mode.as_str() == "a" => {
+            options.append(true).create(true);
+            Permission::Write
+        }
+        _ => return vm.runtime_error("File mode must be \"r\", \"w\" or \"a\"."),
+    };
+    let path = path(vm, args[0], permission)?;
+    let file = match options.open(path.as_str()) {
+        Ok(file) => file,
+        Err(err) => return io_error(vm, "open", path, err),
+    };
+    let class = match this {
+        Value::Class(class) => class,
+        _ => unreachable!(),
+    };
+    let handle = OpenFile(Some(BufReader::new(file)));
+    Ok(vm.new_host(class, Box::new(handle)))
+}
+
+/// Reads the next line without its line ending, or nil at the end of the file
+fn handle_read_line(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    let mut line = String::new();
+    let result = with_handle(vm, this, |file| file.read_line(&mut line))?;
+    match result {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Value::String(vm.intern(line)))
+        }
+        Err(err) => vm.runtime_error(&format!("Could not read file: {}.", err)),
+    }
+}
+
+/// Reads the rest of the file
+fn handle_read(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    let mut content = String::new();
+    match with_handle(vm, this, |file| file.read_to_string(&mut content))? {
+        Ok(_) => Ok(Value::String(vm.intern(content))),
+        Err(err) => vm.runtime_error(&format!("Could not read file: {}.", err)),
+    }
+}
+
+fn handle_write(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let content = args[0].to_string();
+    match with_handle(vm, this, |file| {
+        file.get_mut().write_all(content.as_bytes())
+    })? {
+        Ok(()) => Ok(Value::Nil),
+        Err(err) => vm.runtime_error(&format!("Could not write file: {}.", err)),
+    }
+}
+
+/// Closes the handle now rather than when it's collected. Closing twice does nothing.
+fn handle_close(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    match this {
+        Value::Host(mut host) => match host.data.downcast_mut::<OpenFile>() {
+            Some(OpenFile(file)) => {
+                file.take();
+                Ok(Value::Nil)
+            }
+            None => vm.runtime_error("Expected a file handle."),
+        },
+        _ => vm.runtime_error("Expected a file handle."),
+    }
+}
+
+/// Run an operation on an open handle
+fn with_handle<T>(
+    vm: &Vm,
+    this: Value,
+    operation: impl FnOnce(&mut BufReader<File>) -> T,
+) -> Result<T> {
+    match this {
+        Value::Host(mut host) => match host.data.downcast_mut::<OpenFile>() {
+            Some(OpenFile(Some(file))) => Ok(operation(file)),
+            Some(OpenFile(None)) => vm.runtime_error("File is closed."),
+            None => vm.runtime_error("Expected a file handle."),
+        },
+        _ => vm.runtime_error("Expected a file handle."),
+    }
+}
+
+/// The path argument, once it's checked the VM allows the operation on it
+fn path(vm: &Vm, value: Value, permission: Permission) -> Result<GcRef<LoxString>> {
+    match value {
+        Value::String(path) => {
+            vm.check_permission(permission, Some(path.as_str()))?;
+            Ok(path)
+        }
+        _ => vm.runtime_error("Path must be a string."),
+    }
+}
+
+fn io_error<T>(vm: &Vm, action: &str, path: GcRef<LoxString>, err: std::io::Error) -> Result<T> {
+    vm.runtime_error(&format!(
+        "Could not {} '{}': {}.",
+        action,
+        path.as_str(),
+        err
+    ))
+}
@@ -0,0 +1,263 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{error::Result, value::Value, vm::Vm};
+
+/// `format(template, args...)`, with placeholders like Rust's: `{}` takes the next argument,
+/// `{1}` a given one, and `{:>8.2}` adds a spec of `[[fill]align][+][#][0][width][.precision][type]`
+pub fn define_natives(vm: &mut Vm) {
+    vm.define_variadic_native("format", 1, format);
+}
+
+/// Instances are formatted with the result of this method when their class has one
+const TO_STRING: &str = "toString";
+
+/// The largest width or precision. Rust's own formatting can't go beyond u16::MAX digits, and an
+/// exponent's digits include one before the point.
+const MAX_WIDTH: usize = u16::MAX as usize - 1;
+
+fn format(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let template = match args[0] {
+        Value::String(template) => template,
+        _ => return vm.runtime_error("Format template must be a string."),
+    };
+    let args = &args[1..];
+    let mut output = String::new();
+    let mut next_arg = 0;
+    let mut chars = template.as_str().chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '}' => return vm.runtime_error("Unmatched '}' in format template."),
+            '{' => {
+                let placeholder = match parse_placeholder(&mut chars) {
+                    Ok(placeholder) => placeholder,
+                    Err(message) => return vm.runtime_error(message),
+                };
+                let index = placeholder.index.unwrap_or_else(|| {
+                    next_arg += 1;
+                    next_arg - 1
+                });
+                let value = match args.get(index) {
+                    Some(value) => *value,
+                    None => {
+                        return vm.runtime_error(&format!(
+                            "Format argument {} is out of range, there are {}.",
+                            index,
+                            args.len()
+                        ))
+                    }
+                };
+                let formatted = format_value(vm, value, &placeholder.spec)?;
+                output.push_str(&formatted);
+            }
+            c => output.push(c),
+        }
+    }
+    Ok(Value::String(vm.intern(output)))
+}
+
+struct Placeholder {
+    /// The argument to format, or None for the next one
+    index: Option<usize>,
+    spec: Spec,
+}
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<Align>,
+    sign: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+fn align(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    }
+}
+
+/// Parses the rest of a placeholder, after its '{'
+fn parse_placeholder(
+    chars: &mut Peekable<Chars>,
+) -> std::result::Result<Placeholder, &'static str> {
+    let index = parse_number(chars);
+    let mut spec = Spec::default();
+    if chars.peek() == Some(&':') {
+        chars.next();
+
+        // A fill character must be followed by an alignment
+        let mut lookahead = chars.clone();
+        match (lookahead.next(), lookahead.next()) {
+            (Some(fill), Some(c)) if fill != '}' && align(c).is_some() => {
+                spec.fill = Some(fill);
+                spec.align = align(c);
+                chars.next();
+                chars.next();
+            }
+            (Some(c), _) if align(c).is_some() => {
+                spec.align = align(c);
+                chars.next();
+            }
+            _ => {}
+        }
+
+        if chars.next_if_eq(&'+').is_some() {
+            spec.sign = true;
+        }
+        if chars.next_if_eq(&'#').is_some() {
+            spec.alternate = true;
+        }
+        if chars.next_if_eq(&'0').is_some() {
+            spec.zero = true;
+        }
+        spec.width = parse_number(chars).unwrap_or(0);
+        if spec.width > MAX_WIDTH {
+            return Err("Format width too large.");
+        }
+        if chars.next_if_eq(&'.').is_some() {
+            let precision = parse_number(chars).ok_or("Expected a precision after '.'.")?;
+            if precision > MAX_WIDTH {
+                return Err("Format precision too large.");
+            }
+            spec.precision = Some(precision);
+        }
+        spec.kind = chars.next_if(|c| "xXobe".contains(*c));
+    }
+    match chars.next() {
+        Some('}') => Ok(Placeholder { index, spec }),
+        Some(_) => Err("Invalid format specifier."),
+        None => Err("Unterminated '{' in format template."),
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Option<usize> {
+    let mut number: Option<usize> = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        chars.next();
+        number = Some(
+            number
+                .unwrap_or(0)
+                .saturating_mul(10)
+                .saturating_add(digit as usize),
+        );
+    }
+    number
+}
+
+fn format_value(vm: &mut Vm, value: Value, spec: &Spec) -> Result<String> {
+    // The sign is kept apart so zero padding can go between it and the digits
+    let (sign, body) = match (value, spec.kind) {
+        (Value::Int(x), Some(kind @ ('x' | 'X' | 'o' | 'b'))) => {
+            let magnitude = x.unsigned_abs();
+            let (prefix, digits) = match kind {
+                'x' => ("0x", format!("{:x}", magnitude)),
+                'X' => ("0x", format!("{:X}", magnitude)),
+                'o' => ("0o", format!("{:o}", magnitude)),
+                _ => ("0b", format!("{:b}", magnitude)),
+            };
+            let prefix = if spec.alternate { prefix } else { "" };
+            (sign(x < 0, spec), format!("{}{}", prefix, digits))
+        }
+        (_, Some(kind @ ('x' | 'X' | 'o' | 'b'))) => {
+            return vm.runtime_error(&format!("Format type '{}' requires an integer.", kind))
+        }
+        (Value::Int(_) | Value::Number(_), Some('e')) => {
+            let x = value.as_f64().unwrap();
+            let body = match spec.precision {
+                Some(precision) => format!("{:.*e}", precision, x.abs()),
+                None => format!("{:e}", x.abs()),
+            };
+            (sign(x.is_sign_negative() && !x.is_nan(), spec), body)
+        }
+        (_, Some('e')) => return vm.runtime_error("Format type 'e' requires a number."),
+        (Value::Int(x), _) => match spec.precision {
+            Some(precision) => (
+                sign(x < 0, spec),
+                format!("{:.*}", precision, x.unsigned_abs() as f64),
+            ),
+            None => (sign(x < 0, spec), x.unsigned_abs().to_string()),
+        },
+        (Value::Number(x), _) => {
+            let body = match spec.precision {
+                Some(precision) => format!("{:.*}", precision, x.abs()),
+                None => x.abs().to_string(),
+            };
+            (sign(x.is_sign_negative() && !x.is_nan(), spec), body)
+        }
+        _ => {
+            let mut string = to_string(vm, value)?;
+            if let Some(precision) = spec.precision {
+                string = string.chars().take(precision).collect();
+            }
+            ("", string)
+        }
+    };
+
+    let is_number = matches!(value, Value::Int(_) | Value::Number(_));
+    let len = sign.chars().count() + body.chars().count();
+    let padding = spec.width.saturating_sub(len);
+    if spec.zero && is_number && spec.align.is_none() {
+        return Ok(format!("{}{}{}", sign, "0".repeat(padding), body));
+    }
+
+    // Numbers line up on the right by default, everything else on the left
+    let default_align = if is_number { Align::Right } else { Align::Left };
+    let (before, after) = match spec.align.unwrap_or(default_align) {
+        Align::Left => (0, padding),
+        Align::Center => (padding / 2, padding - padding / 2),
+        Align::Right => (padding, 0),
+    };
+    let fill = spec.fill.unwrap_or(' ').to_string();
+    Ok(format!(
+        "{}{}{}{}",
+        fill.repeat(before),
+        sign,
+        body,
+        fill.repeat(after)
+    ))
+}
+
+fn sign(is_negative: bool, spec: &Spec) -> &'static str {
+    if is_negative {
+        "-"
+    } else if spec.sign {
+        "+"
+    } else {
+        ""
+    }
+}
+
+/// The text for a value, from its class's toString() method if it has one
+fn to_string(vm: &mut Vm, value: Value) -> Result<String> {
+    if let Value::Instance(instance) = value {
+        let name = vm.intern(TO_STRING.to_string());
+        if let Some(method) = instance.class.methods.get(name) {
+            return match vm.call_method(value, method, &[])? {
+                Value::String(string) => Ok(string.as_str().to_string()),
+                _ => vm.runtime_error("toString() must return a string."),
+            };
+        }
+    }
+    Ok(value.to_string())
+}
@@ -7,8 +7,8 @@ use std::{
 
 use crate::{
     obj::{
-        hash_string, BoundMethod, Class, Closure, Function, Instance, LoxString, NativeFunction,
-        ObjectType, Upvalue,
+        hash_string, BoundMethod, Class, Closure, Fiber, Function, Host, Instance, List, LoxString,
+        NativeFunction, ObjectType, Upvalue,
     },
     table::Table,
     value::Value,
@@ -26,6 +26,9 @@ impl HeaderPtr {
             ObjectType::Class => mem::size_of::<Class>(),
             ObjectType::Instance => mem::size_of::<Instance>(),
             ObjectType::BoundMethod => mem::size_of::<BoundMethod>(),
+            ObjectType::Fiber => mem::size_of::<Fiber>(),
+            ObjectType::List => mem::size_of::<List>(),
+            ObjectType::Host => mem::size_of::<Host>(),
         }
     }
 
@@ -44,6 +47,9 @@ impl HeaderPtr {
             ObjectType::Class => self.transmute::<Class>().drop_ptr(),
             ObjectType::Instance => self.transmute::<Instance>().drop_ptr(),
             ObjectType::BoundMethod => self.transmute::<BoundMethod>().drop_ptr(),
+            ObjectType::Fiber => self.transmute::<Fiber>().drop_ptr(),
+            ObjectType::List => self.transmute::<List>().drop_ptr(),
+            ObjectType::Host => self.transmute::<Host>().drop_ptr(),
         }
     }
 }
@@ -81,6 +87,9 @@ impl Display for HeaderPtr {
             ObjectType::Class => self.transmute::<Class>().fmt(f),
             ObjectType::Instance => self.transmute::<Instance>().fmt(f),
             ObjectType::BoundMethod => self.transmute::<BoundMethod>().fmt(f),
+            ObjectType::Fiber => self.transmute::<Fiber>().fmt(f),
+            ObjectType::List => self.transmute::<List>().fmt(f),
+            ObjectType::Host => self.transmute::<Host>().fmt(f),
         }
     }
 }
@@ -308,10 +317,13 @@ impl Gc {
                 // No outgoing references
             }
             ObjectType::Upvalue => {
-                let upvalue = obj.transmute::<Upvalue>();
+                let mut upvalue = obj.transmute::<Upvalue>();
                 // Only closed over values which are no longer on the stack need to be garbage collected
                 if let Some(mut closed) = upvalue.closed {
                     closed.mark_gray(self);
+                } else {
+                    // An open upvalue keeps the stack it points into alive
+                    upvalue.fiber.mark_gray(self);
                 }
             }
             ObjectType::Function => {
@@ -329,22 +341,66 @@ impl Gc {
                 for i in 0..closure.upvalues.len() {
                     closure.upvalues[i].mark_gray(self);
                 }
+                if let Some(mut home_class) = closure.home_class {
+                    home_class.mark_gray(self);
+                }
             }
             ObjectType::Class => {
                 let mut class = obj.transmute::<Class>();
                 class.name.mark_gray(self);
                 class.methods.mark_gray(self);
+                class.getters.mark_gray(self);
+                class.setters.mark_gray(self);
+                class.private_methods.mark_gray(self);
+                class.statics.mark_gray(self);
+                if let Some(mut superclass) = class.superclass {
+                    superclass.mark_gray(self);
+                }
+                for t in class.traits.iter_mut() {
+                    t.mark_gray(self);
+                }
+                for (name, variant) in class.variants.iter_mut() {
+                    name.mark_gray(self);
+                    variant.mark_gray(self);
+                }
             }
             ObjectType::Instance => {
                 let mut instance = obj.transmute::<Instance>();
                 instance.class.mark_gray(self);
                 instance.fields.mark_gray(self);
+                for (class, fields) in instance.private_fields.iter_mut() {
+                    class.mark_gray(self);
+                    fields.mark_gray(self);
+                }
             }
             ObjectType::BoundMethod => {
                 let mut bound = obj.transmute::<BoundMethod>();
                 bound.receiver.mark_gray(self);
                 bound.method.mark_gray(self);
             }
+            ObjectType::Fiber => {
+                let mut fiber = obj.transmute::<Fiber>();
+                fiber.stack.mark_gray(self);
+                fiber.frames.mark_gray(self);
+                let mut next = fiber.open_upvalues;
+                while let Some(mut upvalue) = next {
+                    upvalue.mark_gray(self);
+                    next = upvalue.next;
+                }
+                if let Some(mut caller) = fiber.caller {
+                    caller.mark_gray(self);
+                }
+            }
+            ObjectType::List => {
+                let mut list = obj.transmute::<List>();
+                for item in list.items.iter_mut() {
+                    item.mark_gray(self);
+                }
+            }
+            ObjectType::Host => {
+                let mut host = obj.transmute::<Host>();
+                host.class.mark_gray(self);
+            }
         }
     }
 
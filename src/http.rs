@@ -0,0 +1,389 @@
+use std::{
+    io::BufReader,
+    net::{Shutdown, TcpStream},
+};
+
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{Class, Instance, List},
+    tcp,
+    value::Value,
+    vm::Vm,
+};
+
+/// The `http` module, a minimal HTTP/1.1 server for test doubles and small tools
+pub fn define_natives(vm: &mut Vm) {
+    let mut http = vm.define_class("http");
+    let (name, serve) = vm.new_variadic_native("serve", 2, serve);
+    http.statics.insert(name, serve);
+    let (name, response) = vm.new_variadic_native("response", 1, response);
+    http.statics.insert(name, response);
+
+    let classes = [REQUEST_CLASS, RESPONSE_CLASS].map(|name| {
+        let name = vm.intern(name.to_string());
+        vm.push_root(Value::String(name));
+        let class = vm.alloc(Class::new(name));
+        vm.pop_root();
+        http.statics.insert(name, Value::Class(class));
+        class
+    });
+    vm.define_method_native(classes[0], "header", 1, request_header);
+}
+
+/// The class of the requests handlers are called with, which have the fields `method`, `path`,
+/// `query` (nil without a '?'), `version`, `headers` as a list of `[name, value]` pairs, and
+/// `body`
+const REQUEST_CLASS: &str = "Request";
+/// The class of the instances made by `http.response()`
+const RESPONSE_CLASS: &str = "Response";
+
+/// Requests with more headers than this are refused
+const MAX_HEADERS: usize = 100;
+/// Requests with a larger body than this are refused
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// `http.serve(listener, handler, limit)` answers requests to a `tcp.Listener` with what the
+/// handler returns for them, until it's answered `limit` of them, or forever if that's nil.
+///
+/// The handler can return a string for a 200 response, nil for a 204 one, or an instance with
+/// `status`, `body` and `headers` fields like those from `http.response()`. Every response closes
+/// its connection. A runtime error in the handler is answered with a 500 response, then stops the
+/// server.
+fn serve(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(3, args)?;
+    let (listener, handler) = (args[0], args[1]);
+    let limit = match args.get(2) {
+        None | Some(Value::Nil) => None,
+        Some(Value::Int(limit)) if *limit >= 0 => Some(*limit),
+        Some(_) => return vm.runtime_error("Request limit must be a non-negative integer."),
+    };
+    let request_class = http_class(vm, this, REQUEST_CLASS)?;
+
+    let mut served = 0;
+    while limit.is_none_or(|limit| served < limit) {
+        let mut stream = BufReader::new(tcp::accept(vm, listener)?);
+        let request = match read_request(vm, &mut stream)? {
+            Ok(Some(request)) => request,
+            // The client went away without asking for anything
+            Ok(None) => continue,
+            Err((status, message)) => {
+                respond(vm, &mut stream, status, &[], message)?;
+                served += 1;
+                continue;
+            }
+        };
+        served += 1;
+
+        let request = request.into_value(vm, request_class);
+        let response = match vm.call_function(handler, &[request]) {
+            Ok(response) => response,
+            Err(err) => {
+                respond(vm, &mut stream, 500, &[], "")?;
+                return Err(err);
+            }
+        };
+        vm.push_root(response);
+        let response = to_response(vm, response);
+        vm.pop_root();
+        match response {
+            Ok((status, headers, body)) => respond(vm, &mut stream, status, &headers, &body)?,
+            Err(err) => {
+                respond(vm, &mut stream, 500, &[], "")?;
+                return Err(err);
+            }
+        }
+    }
+    Ok(Value::Nil)
+}
+
+/// `http.response(status, body, headers)`, where the body defaults to empty and the headers are
+/// a list of `[name, value]` pairs
+fn response(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(3, args)?;
+    status(vm, Some(args[0]))?;
+    let class = http_class(vm, this, RESPONSE_CLASS)?;
+    let response = vm.alloc(Instance::new(class));
+    vm.push_root(Value::Instance(response));
+    let body = args.get(1).copied().unwrap_or(Value::Nil);
+    let headers = args.get(2).copied().unwrap_or(Value::Nil);
+    for (name, value) in [("status", args[0]), ("body", body), ("headers", headers)] {
+        set_field(vm, response, name, value);
+    }
+    vm.pop_root();
+    Ok(Value::Instance(response))
+}
+
+/// `request.header(name)`, the value of the first header with the name, whatever its case, or nil
+fn request_header(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let name = match args[0] {
+        Value::String(name) => name,
+        _ => return vm.runtime_error("Header name must be a string."),
+    };
+    let headers = match get_field(vm, this, "headers") {
+        Some(Value::List(headers)) => headers,
+        _ => return Ok(Value::Nil),
+    };
+    for header in &headers.items {
+        if let Value::List(pair) = header {
+            if let [Value::String(key), value] = pair.items[..] {
+                if key.as_str().eq_ignore_ascii_case(name.as_str()) {
+                    return Ok(value);
+                }
+            }
+        }
+    }
+    Ok(Value::Nil)
+}
+
+/// Header names and values, in the order they were sent
+type Headers = Vec<(String, String)>;
+
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    version: String,
+    headers: Headers,
+    body: String,
+}
+
+impl Request {
+    fn into_value(self, vm: &mut Vm, class: GcRef<Class>) -> Value {
+        let request = vm.alloc(Instance::new(class));
+        // Keep the request reachable while its fields are allocated
+        vm.push_root(Value::Instance(request));
+        for (name, value) in [
+            ("method", Some(self.method)),
+            ("path", Some(self.path)),
+            ("query", self.query),
+            ("version", Some(self.version)),
+            ("body", Some(self.body)),
+        ] {
+            let value = match value {
+                Some(value) => Value::String(vm.intern(value)),
+                None => Value::Nil,
+            };
+            vm.push_root(value);
+            set_field(vm, request, name, value);
+            vm.pop_root();
+        }
+        let mut headers = vm.alloc(List::new(Vec::with_capacity(self.headers.len())));
+        vm.push_root(Value::List(headers));
+        set_field(vm, request, "headers", Value::List(headers));
+        vm.pop_root();
+        for (name, value) in self.headers {
+            let header = vm.new_string_list(vec![name, value]);
+            headers.items.push(header);
+        }
+        vm.pop_root();
+        Value::Instance(request)
+    }
+}
+
+/// The next request on the connection, None if it closes first, or the status and message to
+/// refuse it with
+fn read_request(
+    vm: &Vm,
+    stream: &mut BufReader<TcpStream>,
+) -> Result<std::result::Result<Option<Request>, (u16, &'static str)>> {
+    const BAD_REQUEST: (u16, &str) = (400, "Bad Request");
+    let line = match tcp::read_line(vm, stream)? {
+        Ok(Some(line)) => line,
+        Ok(None) | Err(_) => return Ok(Ok(None)),
+    };
+    let (method, target, version) = match line.split(' ').collect::<Vec<_>>()[..] {
+        [method, target, version] if version.starts_with("HTTP/1.") => (method, target, version),
+        _ => return Ok(Err(BAD_REQUEST)),
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None),
+    };
+
+    let mut headers = vec![];
+    loop {
+        let line = match tcp::read_line(vm, stream)? {
+            Ok(Some(line)) => line,
+            Ok(None) | Err(_) => return Ok(Err(BAD_REQUEST)),
+        };
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Ok(Err((431, "Request Header Fields Too Large")));
+        }
+        match line.split_once(':') {
+            Some((name, value)) if !name.is_empty() && !name.contains(' ') => {
+                headers.push((name.to_string(), value.trim().to_string()))
+            }
+            _ => return Ok(Err(BAD_REQUEST)),
+        }
+    }
+
+    let header = |wanted: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.as_str())
+    };
+    if header("Transfer-Encoding").is_some() {
+        return Ok(Err((501, "Not Implemented")));
+    }
+    let length = match header("Content-Length").map(str::parse::<usize>) {
+        None => 0,
+        Some(Ok(length)) if length <= MAX_BODY => length,
+        Some(Ok(_)) => return Ok(Err((413, "Content Too Large"))),
+        Some(Err(_)) => return Ok(Err(BAD_REQUEST)),
+    };
+    let mut body = vec![0; length];
+    if tcp::read_exact(vm, stream, &mut body)?.is_err() {
+        return Ok(Err(BAD_REQUEST));
+    }
+
+    Ok(Ok(Some(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        version: version.to_string(),
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })))
+}
+
+/// The status, headers and body of what a handler returned
+fn to_response(vm: &mut Vm, value: Value) -> Result<(u16, Headers, String)> {
+    match value {
+        Value::String(body) => Ok((200, vec![], body.as_str().to_string())),
+        Value::Nil => Ok((204, vec![], String::new())),
+        Value::Instance(_) => {
+            let status = get_field(vm, value, "status");
+            let status = status.filter(|status| !matches!(status, Value::Nil));
+            let status = match status {
+                None => 200,
+                status => self::status(vm, status)?,
+            };
+            let body = match get_field(vm, value, "body") {
+                None | Some(Value::Nil) => String::new(),
+                Some(Value::String(body)) => body.as_str().to_string(),
+                Some(body) => body.to_string(),
+            };
+            let headers = match get_field(vm, value, "headers") {
+                None | Some(Value::Nil) => vec![],
+                Some(Value::List(headers)) => {
+                    let mut pairs = vec![];
+                    for header in &headers.items {
+                        match header {
+                            Value::List(pair) => match pair.items[..] {
+                                [Value::String(name), value] => {
+                                    pairs.push((name.as_str().to_string(), value.to_string()))
+                                }
+                                _ => return headers_error(vm),
+                            },
+                            _ => return headers_error(vm),
+                        }
+                    }
+                    pairs
+                }
+                Some(_) => return headers_error(vm),
+            };
+            Ok((status, headers, body))
+        }
+        _ => vm.runtime_error("HTTP handler must return a string, a response or nil."),
+    }
+}
+
+fn headers_error<T>(vm: &Vm) -> Result<T> {
+    vm.runtime_error("Response headers must be a list of [name, value] pairs.")
+}
+
+fn status(vm: &Vm, status: Option<Value>) -> Result<u16> {
+    match status {
+        Some(Value::Int(status)) if (100..=599).contains(&status) => Ok(status as u16),
+        _ => vm.runtime_error("Response status must be an integer from 100 to 599."),
+    }
+}
+
+/// Writes a response and closes the connection. A client that's gone away isn't an error.
+fn respond(
+    vm: &Vm,
+    stream: &mut BufReader<TcpStream>,
+    status: u16,
+    headers: &[(String, String)],
+    body: &str,
+) -> Result<()> {
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason(status));
+    let has_header = |wanted: &str| {
+        headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(wanted))
+    };
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    // These statuses never have a body
+    let body = if matches!(status, 100..=199 | 204 | 304) {
+        ""
+    } else {
+        if !has_header("Content-Type") && !body.is_empty() {
+            response.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+        }
+        response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        body
+    };
+    response.push_str("Connection: close\r\n\r\n");
+    response.push_str(body);
+    if tcp::write_all(vm, stream, response.as_bytes())?.is_ok() {
+        let _ = stream.get_ref().shutdown(Shutdown::Write);
+    }
+    Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+/// A class from the statics of the `http` class
+fn http_class(vm: &mut Vm, http: Value, name: &str) -> Result<GcRef<Class>> {
+    let name = vm.intern(name.to_string());
+    match http {
+        Value::Class(http) => match http.statics.get(name) {
+            Some(Value::Class(class)) => Ok(class),
+            _ => vm.runtime_error(&format!("http.{} must be a class.", name.as_str())),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn get_field(vm: &mut Vm, instance: Value, name: &str) -> Option<Value> {
+    let name = vm.intern(name.to_string());
+    match instance {
+        Value::Instance(instance) => instance.fields.get(name),
+        _ => None,
+    }
+}
+
+fn set_field(vm: &mut Vm, mut instance: GcRef<Instance>, name: &str, value: Value) {
+    let name = vm.intern(name.to_string());
+    instance.fields.insert(name, value);
+}
@@ -0,0 +1,445 @@
+use std::{fmt::Write, ops::Deref, str::Chars};
+
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{Class, Instance, List},
+    value::Value,
+    vm::Vm,
+};
+
+/// The `json` module. Objects parse to instances of `json.Object`, and any instance stringifies
+/// to an object of its fields.
+pub fn define_natives(vm: &mut Vm) {
+    let mut json = vm.define_class("json");
+    vm.define_static_native(json, "parse", 1, parse);
+    let (name, stringify) = vm.new_variadic_native("stringify", 1, stringify);
+    json.statics.insert(name, stringify);
+
+    let object_name = vm.intern("Object".to_string());
+    vm.push_root(Value::String(object_name));
+    let object_class = vm.alloc(Class::new(object_name));
+    vm.pop_root();
+    json.statics.insert(object_name, Value::Class(object_class));
+}
+
+/// JSON nests arbitrarily, but the parser and stringifier recurse
+const MAX_DEPTH: usize = 512;
+
+fn parse(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let source = match args[0] {
+        Value::String(source) => source,
+        _ => return vm.runtime_error("JSON source must be a string."),
+    };
+    let json = match Parser::new(source.as_str()).parse() {
+        Ok(json) => json,
+        Err(message) => return vm.runtime_error(&message),
+    };
+    let object_name = vm.intern("Object".to_string());
+    let object_class = match this {
+        Value::Class(json_class) => match json_class.statics.get(object_name) {
+            Some(Value::Class(class)) => class,
+            _ => return vm.runtime_error("json.Object must be a class."),
+        },
+        _ => unreachable!(),
+    };
+    Ok(to_value(vm, json, object_class))
+}
+
+/// `json.stringify(value, indent)` indents nested values by the given number of spaces, or writes
+/// everything on one line when the indent is nil or left out
+fn stringify(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(2, args)?;
+    let indent = match args.get(1) {
+        None | Some(Value::Nil) => None,
+        Some(Value::Int(indent)) if (0..=10).contains(indent) => Some(*indent as usize),
+        _ => return vm.runtime_error("Indent must be an integer from 0 to 10."),
+    };
+    let mut stringifier = Stringifier {
+        indent,
+        output: String::new(),
+        parents: vec![],
+    };
+    match stringifier.write(args[0]) {
+        Ok(()) => Ok(Value::String(vm.intern(stringifier.output))),
+        Err(message) => vm.runtime_error(&message),
+    }
+}
+
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Build the Lox value for parsed JSON, keeping each container reachable while it's filled in
+fn to_value(vm: &mut Vm, json: Json, object_class: GcRef<Class>) -> Value {
+    match json {
+        Json::Null => Value::Nil,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(x) => Value::Number(x),
+        Json::String(string) => Value::String(vm.intern(string)),
+        Json::Array(items) => {
+            let mut list = vm.alloc(List::new(Vec::with_capacity(items.len())));
+            vm.push_root(Value::List(list));
+            for item in items {
+                let item = to_value(vm, item, object_class);
+                list.items.push(item);
+            }
+            vm.pop_root()
+        }
+        Json::Object(members) => {
+            let mut instance = vm.alloc(Instance::new(object_class));
+            vm.push_root(Value::Instance(instance));
+            for (key, value) in members {
+                let value = to_value(vm, value, object_class);
+                vm.push_root(value);
+                let key = vm.intern(key);
+                vm.pop_root();
+                instance.fields.insert(key, value);
+            }
+            vm.pop_root()
+        }
+    }
+}
+
+struct Parser<'source> {
+    chars: Chars<'source>,
+    line: usize,
+    column: usize,
+    depth: usize,
+}
+
+impl<'source> Parser<'source> {
+    fn new(source: &'source str) -> Self {
+        Self {
+            chars: source.chars(),
+            line: 1,
+            column: 1,
+            depth: 0,
+        }
+    }
+
+    fn parse(&mut self) -> std::result::Result<Json, String> {
+        let json = self.value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(json),
+            Some(_) => Err(self.error("Unexpected text after the JSON value")),
+        }
+    }
+
+    fn value(&mut self) -> std::result::Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => self.nested(Self::array),
+            Some('{') => self.nested(Self::object),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("Expected a value")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> std::result::Result<Json, String>,
+    ) -> std::result::Result<Json, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Too deeply nested"));
+        }
+        self.depth += 1;
+        let json = parse(self);
+        self.depth -= 1;
+        json
+    }
+
+    fn array(&mut self) -> std::result::Result<Json, String> {
+        self.advance();
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => {}
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err(self.error("Expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> std::result::Result<Json, String> {
+        self.advance();
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.advance() != Some(':') {
+                return Err(self.error("Expected ':' after object key"));
+            }
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => {}
+                Some('}') => return Ok(Json::Object(members)),
+                _ => return Err(self.error("Expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> std::result::Result<String, String> {
+        self.advance();
+        let mut string = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.advance() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape in string")),
+                    };
+                    string.push(escaped);
+                }
+                Some(c) if c < ' ' => return Err(self.error("Control character in string")),
+                Some(c) => string.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    /// The character for a `\uXXXX` escape, which may be the first half of a surrogate pair
+    fn unicode_escape(&mut self) -> std::result::Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("Invalid unicode escape"));
+        }
+        if self.advance() != Some('\\') || self.advance() != Some('u') {
+            return Err(self.error("Expected the second half of a surrogate pair"));
+        }
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("Invalid surrogate pair"));
+        }
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        char::from_u32(code).ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> std::result::Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.error("Expected four hex digits in unicode escape")),
+            }
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> std::result::Result<Json, String> {
+        let mut text = String::new();
+        if self.peek() == Some('-') {
+            text.push('-');
+            self.advance();
+        }
+        match self.peek() {
+            Some('0') => text.push(self.advance().unwrap()),
+            Some(c) if c.is_ascii_digit() => self.digits(&mut text),
+            _ => return Err(self.error("Expected a digit")),
+        }
+        if self.peek() == Some('.') {
+            text.push(self.advance().unwrap());
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("Expected a digit after '.'"));
+            }
+            self.digits(&mut text);
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            text.push(e);
+            self.advance();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                text.push(sign);
+                self.advance();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("Expected a digit in exponent"));
+            }
+            self.digits(&mut text);
+        }
+        // The text is always a valid float by now
+        Ok(Json::Number(text.parse().unwrap()))
+    }
+
+    fn digits(&mut self, text: &mut String) {
+        while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+            text.push(c);
+            self.advance();
+        }
+    }
+
+    fn literal(&mut self, word: &str, json: Json) -> std::result::Result<Json, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(self.error("Expected a value"));
+            }
+            self.advance();
+        }
+        Ok(json)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.advance();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.clone().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!(
+            "Invalid JSON at line {}, column {}: {}.",
+            self.line, self.column, message
+        )
+    }
+}
+
+struct Stringifier {
+    indent: Option<usize>,
+    output: String,
+    /// The lists and instances being written, to detect cycles
+    parents: Vec<*const ()>,
+}
+
+impl Stringifier {
+    fn write(&mut self, value: Value) -> std::result::Result<(), String> {
+        match value {
+            Value::Nil => self.output.push_str("null"),
+            Value::Bool(b) => write!(self.output, "{}", b).unwrap(),
+            Value::Int(x) => write!(self.output, "{}", x).unwrap(),
+            Value::Number(x) if x.is_finite() => write!(self.output, "{}", x).unwrap(),
+            Value::Number(x) => return Err(format!("Cannot convert {} to JSON.", x)),
+            Value::String(string) => self.write_string(string.as_str()),
+            Value::List(list) => {
+                self.enter(list.deref() as *const List as *const ())?;
+                self.output.push('[');
+                for (i, item) in list.items.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push(',');
+                    }
+                    self.newline();
+                    self.write(*item)?;
+                }
+                self.leave(!list.items.is_empty());
+                self.output.push(']');
+            }
+            Value::Instance(instance) => {
+                self.enter(instance.deref() as *const Instance as *const ())?;
+                // Sorted so the output doesn't depend on hashing
+                let mut fields: Vec<_> = instance.fields.iter().collect();
+                fields.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+                self.output.push('{');
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push(',');
+                    }
+                    self.newline();
+                    self.write_string(name.as_str());
+                    self.output.push(':');
+                    if self.indent.is_some() {
+                        self.output.push(' ');
+                    }
+                    self.write(*value)?;
+                }
+                self.leave(!fields.is_empty());
+                self.output.push('}');
+            }
+            _ => return Err(format!("Cannot convert {} to JSON.", value)),
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self, object: *const ()) -> std::result::Result<(), String> {
+        if self.parents.contains(&object) {
+            return Err("Cannot convert a value containing itself to JSON.".to_string());
+        }
+        if self.parents.len() == MAX_DEPTH {
+            return Err("Value is too deeply nested to convert to JSON.".to_string());
+        }
+        self.parents.push(object);
+        Ok(())
+    }
+
+    /// Close a list or object, putting the closing bracket on its own line if it had items
+    fn leave(&mut self, had_items: bool) {
+        self.parents.pop();
+        if had_items {
+            self.newline();
+        }
+    }
+
+    /// Start a line at the current depth when indenting
+    fn newline(&mut self) {
+        if let Some(indent) = self.indent {
+            self.output.push('\n');
+            for _ in 0..indent * self.parents.len() {
+                self.output.push(' ');
+            }
+        }
+    }
+
+    fn write_string(&mut self, string: &str) {
+        self.output.push('"');
+        for c in string.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                c if c < ' ' => write!(self.output, "\\u{:04x}", c as u32).unwrap(),
+                c => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+}
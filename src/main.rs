@@ -1,10 +1,14 @@
 use std::{
     env, fs,
     io::{self, Write},
-    process,
+    path::PathBuf,
+    process::exit,
 };
 
 use error::LoxError;
+use permissions::{Access, Permissions};
+use signal_hook::consts::SIGINT;
+use time::Clock;
 use vm::Vm;
 
 mod chunk;
@@ -12,13 +16,24 @@ mod compiler;
 #[cfg(any(feature = "debug_trace_execution", feature = "debug_print_code"))]
 mod disassembler;
 mod error;
+mod file;
+mod format;
 mod gc;
+mod http;
+mod json;
 mod obj;
 mod op_code;
+mod os;
 mod parser;
+mod permissions;
+mod process;
+mod reflect;
+mod regex;
 mod scanner;
 mod stack;
 mod table;
+mod tcp;
+mod time;
 mod value;
 mod vm;
 
@@ -33,7 +48,9 @@ fn repl(vm: &mut Vm) {
         if line.is_empty() {
             break;
         }
-        vm.interpret(&line).ok();
+        if let Err(LoxError::Exit(code)) = vm.interpret(&line) {
+            exit(code);
+        }
     }
 }
 
@@ -42,31 +59,93 @@ fn run_file(vm: &mut Vm, path: &str) {
         Ok(content) => content,
         Err(error) => {
             eprint!("Unable to read file {}: {}", path, error);
-            process::exit(74);
+            exit(74);
         }
     };
     if let Err(error) = vm.interpret(&code) {
         match error {
             LoxError::CompileError(_) => {
-                process::exit(65);
+                exit(65);
             }
             LoxError::RuntimeError => {
                 eprintln!("Runtime error.");
-                process::exit(70);
+                exit(70);
+            }
+            LoxError::PermissionDenied => {
+                eprintln!("Permission denied.");
+                exit(77);
             }
+            LoxError::Interrupted => exit(130),
+            LoxError::Exit(code) => exit(code),
         }
     }
 }
 
+fn usage() -> ! {
+    eprintln!("Usage: clox [options] [path [args...]]");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --allow-<permission>        Allow read, write, env, run, net or all");
+    eprintln!("  --deny-<permission>         Deny read, write, env, run, net or all");
+    eprintln!("  --allow-read=<path>[,...]   Only allow reading these files and directories");
+    eprintln!("  --allow-write=<path>[,...]  Only allow writing these files and directories");
+    eprintln!("  --time=<seconds>            Fix the time at these seconds since the epoch");
+    eprintln!();
+    eprintln!("Reading and writing files and the environment are allowed unless denied.");
+    exit(64);
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut vm = Vm::new();
-    match args.len() {
-        1 => repl(&mut vm),
-        2 => run_file(&mut vm, &args[1]),
-        _ => {
-            eprintln!("Usage: clox [path]");
-            process::exit(64);
+    let mut permissions = Permissions::default();
+    let mut clock = None;
+    let mut args = env::args().skip(1).peekable();
+    // Options come before the script's path
+    while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+        match option.split_once('=') {
+            None => match option.as_str() {
+                "--allow-all" => permissions = Permissions::all(),
+                "--deny-all" => permissions = Permissions::none(),
+                "--allow-read" => permissions.read = Access::Allowed,
+                "--deny-read" => permissions.read = Access::Denied,
+                "--allow-write" => permissions.write = Access::Allowed,
+                "--deny-write" => permissions.write = Access::Denied,
+                "--allow-env" => permissions.env = true,
+                "--deny-env" => permissions.env = false,
+                "--allow-run" => permissions.run = true,
+                "--deny-run" => permissions.run = false,
+                "--allow-net" => permissions.net = true,
+                "--deny-net" => permissions.net = false,
+                _ => usage(),
+            },
+            Some(("--allow-read", paths)) => permissions
+                .read
+                .allow_paths(paths.split(',').map(PathBuf::from)),
+            Some(("--allow-write", paths)) => permissions
+                .write
+                .allow_paths(paths.split(',').map(PathBuf::from)),
+            // Scripts see this time, which only moves forward when they sleep
+            Some(("--time", seconds)) => match seconds.parse() {
+                Ok(seconds) => clock = Some(Clock::manual(seconds)),
+                Err(_) => usage(),
+            },
+            _ => usage(),
         }
     }
+
+    let mut vm = Vm::new(permissions);
+    if let Some(clock) = clock {
+        vm.clock = clock;
+    }
+    // Ctrl-C stops the running script, or exits if it's pressed again before the script stops
+    let interrupted = vm.interrupt_handle();
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 130, interrupted.clone())
+        .and_then(|_| signal_hook::flag::register(SIGINT, interrupted))
+        .expect("Unable to handle Ctrl-C");
+    // Everything after the script's path is passed to the script
+    let path = args.next();
+    os::define_script_globals(&mut vm, path.as_deref(), args.collect());
+    match path {
+        None => repl(&mut vm),
+        Some(path) => run_file(&mut vm, &path),
+    }
 }
@@ -1,14 +1,18 @@
 use std::{
+    any::Any,
+    cell::RefCell,
     fmt::{self, Display, Formatter, Write},
     ops::Deref,
 };
 
 use crate::{
     chunk::Chunk,
+    error::Result,
     gc::{GcRef, ObjHeader},
+    stack::Stack,
     table::Table,
     value::Value,
-    vm::ValueStack,
+    vm::{CallFrameStack, ValueStack, Vm},
 };
 
 #[derive(Clone, Copy)]
@@ -21,8 +25,12 @@ pub enum ObjectType {
     Class,
     Instance,
     BoundMethod,
+    Fiber,
+    List,
+    Host,
 }
 
+#[repr(C)]
 pub struct LoxString {
     pub header: ObjHeader,
     string: String,
@@ -65,9 +73,18 @@ pub struct FunctionUpvalue {
     pub is_local: bool,
 }
 
+#[repr(C)]
 pub struct Function {
     pub header: ObjHeader,
+    /// The number of named parameters
     pub arity: usize,
+    /// The number of parameters without a default value
+    pub min_arity: usize,
+    /// Whether extra arguments are collected into a rest parameter after the named ones
+    pub variadic: bool,
+    /// Where to start executing, indexed by how many optional parameters were passed. The code for
+    /// each default value comes before the body, so passing more arguments skips more of it.
+    pub entry_points: Vec<usize>,
     pub chunk: Chunk,
     pub name: Option<GcRef<LoxString>>,
     pub upvalues: Vec<FunctionUpvalue>,
@@ -78,11 +95,23 @@ impl Function {
         Self {
             header: ObjHeader::new(ObjectType::Function),
             arity: 0,
+            min_arity: 0,
+            variadic: false,
+            entry_points: vec![],
             chunk: Chunk::new(),
             name,
             upvalues: vec![],
         }
     }
+
+    /// Where to start executing when called with the given number of arguments
+    pub fn entry_point(&self, arg_count: usize) -> usize {
+        if self.entry_points.is_empty() {
+            0
+        } else {
+            self.entry_points[arg_count.min(self.arity) - self.min_arity]
+        }
+    }
 }
 
 impl Display for Function {
@@ -98,17 +127,25 @@ impl Display for Function {
     }
 }
 
-pub type NativeFn = fn(args: &[Value]) -> Value;
+/// `this` is the value in the callee's stack slot: the receiver for native methods, otherwise the
+/// native itself
+pub type NativeFn = fn(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value>;
+#[repr(C)]
 pub struct NativeFunction {
     pub header: ObjHeader,
     pub function: NativeFn,
+    pub arity: usize,
+    /// Whether the native accepts any number of arguments beyond its arity
+    pub variadic: bool,
 }
 
 impl NativeFunction {
-    pub fn new(function: NativeFn) -> Self {
+    pub fn new(function: NativeFn, arity: usize) -> Self {
         Self {
             header: ObjHeader::new(ObjectType::NativeFunction),
             function,
+            arity,
+            variadic: false,
         }
     }
 }
@@ -120,10 +157,13 @@ impl Display for NativeFunction {
     }
 }
 
+#[repr(C)]
 pub struct Closure {
     pub header: ObjHeader,
     pub function: GcRef<Function>,
     pub upvalues: Vec<GcRef<Upvalue>>,
+    /// For trait methods, the class that included them and so supplies their 'super'
+    pub home_class: Option<GcRef<Class>>,
 }
 
 impl Closure {
@@ -133,6 +173,7 @@ impl Closure {
             header: ObjHeader::new(ObjectType::Closure),
             upvalues,
             function,
+            home_class: None,
         }
     }
 }
@@ -143,38 +184,41 @@ impl Display for Closure {
     }
 }
 
+#[repr(C)]
 pub struct Upvalue {
     pub header: ObjHeader,
-    /// Index of the closed-over variable in the locals stack
+    /// The fiber whose stack holds the closed-over variable while the upvalue is open
+    pub fiber: GcRef<Fiber>,
+    /// Index of the closed-over variable in the fiber's locals stack
     pub location: usize,
     pub closed: Option<Value>,
     pub next: Option<GcRef<Upvalue>>,
 }
 
 impl Upvalue {
-    pub fn new(location: usize, next: Option<GcRef<Upvalue>>) -> Self {
+    pub fn new(fiber: GcRef<Fiber>, location: usize, next: Option<GcRef<Upvalue>>) -> Self {
         Self {
             header: ObjHeader::new(ObjectType::Upvalue),
+            fiber,
             location,
             closed: None,
             next,
         }
     }
 
-    pub fn read(&self, stack: &ValueStack) -> Value {
+    pub fn read(&self) -> Value {
         if let Some(closed) = self.closed {
             closed
         } else {
-            *stack.read(self.location)
+            *self.fiber.stack.read(self.location)
         }
     }
 
-    pub fn write(&mut self, stack: &mut ValueStack) {
-        let value = *stack.peek(0);
+    pub fn write(&mut self, value: Value) {
         if self.closed.is_some() {
             self.closed = Some(value);
         } else {
-            stack.write(self.location, value);
+            self.fiber.stack.write(self.location, value);
         }
     }
 }
@@ -185,10 +229,28 @@ impl Display for Upvalue {
     }
 }
 
+#[repr(C)]
 pub struct Class {
     pub header: ObjHeader,
     pub name: GcRef<LoxString>,
     pub methods: Table,
+    /// Methods run when a property is read, e.g. `area { ... }`
+    pub getters: Table,
+    /// Methods run when a property is assigned, e.g. `area=(value) { ... }`
+    pub setters: Table,
+    /// `#name` methods, which aren't inherited
+    pub private_methods: Table,
+    /// Members looked up on the class itself rather than on its instances
+    pub statics: Table,
+    pub superclass: Option<GcRef<Class>>,
+    /// Traits hold methods for classes to include, but can't be instantiated or inherited from
+    pub is_trait: bool,
+    /// The traits included in this class
+    pub traits: Vec<GcRef<Class>>,
+    /// Enums can't be instantiated, and their only instances are their variants
+    pub is_enum: bool,
+    /// An enum's variants and their names, in declaration order
+    pub variants: Vec<(GcRef<LoxString>, GcRef<Instance>)>,
 }
 
 impl Display for Class {
@@ -203,18 +265,42 @@ impl Class {
             header: ObjHeader::new(ObjectType::Class),
             name,
             methods: Table::new(),
+            getters: Table::new(),
+            setters: Table::new(),
+            private_methods: Table::new(),
+            statics: Table::new(),
+            superclass: None,
+            is_trait: false,
+            traits: Vec::new(),
+            is_enum: false,
+            variants: Vec::new(),
         }
     }
+
+    /// Is this class the given class or one of its descendants, or does it include the given trait?
+    pub fn is_subclass_of(&self, class: GcRef<Class>) -> bool {
+        std::ptr::eq(self, class.deref())
+            || self
+                .superclass
+                .is_some_and(|superclass| superclass.is_subclass_of(class))
+            || self.traits.iter().any(|t| t.is_subclass_of(class))
+    }
 }
 
+#[repr(C)]
 pub struct Instance {
     pub header: ObjHeader,
     pub class: GcRef<Class>,
     pub fields: Table,
+    /// `#name` fields, kept apart for each class in the hierarchy that declared some
+    pub private_fields: Vec<(GcRef<Class>, Table)>,
 }
 
 impl Display for Instance {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(variant) = self.variant_name() {
+            return write!(f, "{}.{}", self.class.name.as_str(), variant.as_str());
+        }
         f.write_str(&format!("{} instance", self.class.name.as_str()))
     }
 }
@@ -225,18 +311,54 @@ impl Instance {
             header: ObjHeader::new(ObjectType::Instance),
             class,
             fields: Table::new(),
+            private_fields: Vec::new(),
+        }
+    }
+
+    /// The name of this enum variant, or None if this isn't one
+    pub fn variant_name(&self) -> Option<GcRef<LoxString>> {
+        self.class
+            .variants
+            .iter()
+            .find(|(_, variant)| std::ptr::eq(variant.deref(), self))
+            .map(|(name, _)| *name)
+    }
+
+    pub fn get_private(&self, class: GcRef<Class>, name: GcRef<LoxString>) -> Option<Value> {
+        self.private_fields
+            .iter()
+            .find(|(owner, _)| *owner == class)
+            .and_then(|(_, fields)| fields.get(name))
+    }
+
+    pub fn set_private(&mut self, class: GcRef<Class>, name: GcRef<LoxString>, value: Value) {
+        match self
+            .private_fields
+            .iter_mut()
+            .find(|(owner, _)| *owner == class)
+        {
+            Some((_, fields)) => {
+                fields.insert(name, value);
+            }
+            None => {
+                let mut fields = Table::new();
+                fields.insert(name, value);
+                self.private_fields.push((class, fields));
+            }
         }
     }
 }
 
+#[repr(C)]
 pub struct BoundMethod {
     pub header: ObjHeader,
     pub receiver: Value,
-    pub method: GcRef<Closure>,
+    /// A closure, or a native function
+    pub method: Value,
 }
 
 impl BoundMethod {
-    pub fn new(receiver: Value, method: GcRef<Closure>) -> Self {
+    pub fn new(receiver: Value, method: Value) -> Self {
         Self {
             header: ObjHeader::new(ObjectType::BoundMethod),
             receiver,
@@ -247,6 +369,110 @@ impl BoundMethod {
 
 impl Display for BoundMethod {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        self.method.function.fmt(f)
+        self.method.fmt(f)
+    }
+}
+
+/// A coroutine with its own value stack and call frames
+#[repr(C)]
+pub struct Fiber {
+    pub header: ObjHeader,
+    pub stack: ValueStack,
+    pub frames: CallFrameStack,
+    /// Linked list of upvalues pointing into this fiber's stack, sorted by descending location
+    pub open_upvalues: Option<GcRef<Upvalue>>,
+    /// The fiber that resumed this one. It regains control when this fiber yields or returns.
+    pub caller: Option<GcRef<Fiber>>,
+}
+
+impl Fiber {
+    pub fn new() -> Self {
+        Self {
+            header: ObjHeader::new(ObjectType::Fiber),
+            stack: Stack::new(),
+            frames: Stack::new(),
+            open_upvalues: None,
+            caller: None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frames.len() == 0
+    }
+}
+
+impl Display for Fiber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("<fiber>")
+    }
+}
+
+#[repr(C)]
+pub struct List {
+    pub header: ObjHeader,
+    pub items: Vec<Value>,
+}
+
+impl List {
+    pub fn new(items: Vec<Value>) -> Self {
+        Self {
+            header: ObjHeader::new(ObjectType::List),
+            items,
+        }
+    }
+
+    fn fmt_items(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_char('[')?;
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            Display::fmt(item, f)?;
+        }
+        f.write_char(']')
+    }
+}
+
+thread_local! {
+    /// The lists being displayed, outermost first, so a list containing itself isn't printed forever
+    static DISPLAYING: RefCell<Vec<*const List>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Display for List {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let list = self as *const List;
+        if DISPLAYING.with(|displaying| displaying.borrow().contains(&list)) {
+            return f.write_str("[...]");
+        }
+        DISPLAYING.with(|displaying| displaying.borrow_mut().push(list));
+        let result = self.fmt_items(f);
+        DISPLAYING.with(|displaying| displaying.borrow_mut().pop());
+        result
+    }
+}
+
+/// Data owned by the embedding Rust code, like an open file, with methods from its class. The
+/// data is dropped when the object is collected, so resources are released even if a script
+/// forgets to.
+#[repr(C)]
+pub struct Host {
+    pub header: ObjHeader,
+    pub class: GcRef<Class>,
+    pub data: Box<dyn Any>,
+}
+
+impl Host {
+    pub fn new(class: GcRef<Class>, data: Box<dyn Any>) -> Self {
+        Self {
+            header: ObjHeader::new(ObjectType::Host),
+            class,
+            data,
+        }
+    }
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}>", self.class.name.as_str())
     }
 }
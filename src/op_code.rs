@@ -38,6 +38,17 @@ pub enum OpCode {
     Subtract,
     Multiply,
     Divide,
+    /// Divide, truncating towards zero
+    IntDivide,
+    Modulo,
+    Power,
+
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
 
     Return,
 
@@ -53,10 +64,16 @@ pub enum OpCode {
 
     Print,
     Pop,
+    /// Push a copy of the value on top of the stack
+    Dup,
+    /// Push copies of the top two values on the stack, keeping their order
+    DupPair,
 
     /// Load constant for use to top of stack
     Constant(Constant),
     DefineGlobal(Constant),
+    /// Like DefineGlobal, but the global can't be assigned or defined again
+    DefineConstGlobal(Constant),
     GetGlobal(Constant),
     SetGlobal(Constant),
 
@@ -67,6 +84,8 @@ pub enum OpCode {
     SetUpvalue(UpvalueIndex),
 
     JumpIfFalse(Jump),
+    JumpIfNil(Jump),
+    JumpIfNotNil(Jump),
     Jump(Jump),
     Loop(Jump),
 
@@ -76,12 +95,62 @@ pub enum OpCode {
     Closure(Constant),
     CloseUpvalue,
 
+    /// Pop the given number of values into a new list
+    List {
+        item_count: u8,
+    },
+    /// Pop a list, pushing its items, which must be exactly the given number
+    Unpack {
+        item_count: u8,
+    },
+    /// Pop an index and a list, pushing the item at that index
+    GetIndex,
+    /// Pop a value, an index and a list, storing the value at that index and pushing it back
+    SetIndex,
+
     Class(Constant),
+    Trait(Constant),
+    Enum(Constant),
+    /// Pop the given number of field name and value pairs into a new variant of the enum below them
+    Variant {
+        name: Constant,
+        field_count: u8,
+    },
+    /// Pop the given number of traits, copying their members into the class below them
+    Include {
+        trait_count: u8,
+    },
     GetProperty(Constant),
     SetProperty(Constant),
     Method(Constant),
+    /// Like Method, but defines a getter
+    Getter(Constant),
+    /// Like Method, but defines a setter
+    Setter(Constant),
+    /// Like Method, but defines a method only reachable through 'this' inside the class
+    PrivateMethod(Constant),
+    /// Pop a value and store it as a static member of the class below it on the stack
+    Static(Constant),
     Invoke(Invoke),
     Inherit,
+    /// Pop a class and a value, pushing whether the value is an instance of the class or a subclass
+    IsInstance,
+    /// Pop a value, pushing whether it is an instance with the given field
+    HasField(Constant),
+    /// Pop the class owning the private member, and replace 'this' with the member's value
+    GetPrivate(Constant),
+    /// Pop the class owning the private field, and assign it on 'this'
+    SetPrivate(Constant),
+    /// Pop the class owning the private member, and call it on 'this'
+    InvokePrivate(Invoke),
+    /// Push the superclass of the class the running trait method was included in
+    HomeSuperclass,
     GetSuper(Constant),
+    /// Assign a property of 'this', running the superclass's setter if it has one
+    SetSuper(Constant),
     SuperInvoke(Invoke),
+    /// Like GetSuper, but looks up the superclass's static members
+    GetSuperStatic(Constant),
+    /// Like SuperInvoke, but looks up the superclass's static members
+    SuperInvokeStatic(Invoke),
 }
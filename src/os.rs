@@ -0,0 +1,109 @@
+use std::{
+    env,
+    io::{self, BufRead, Read},
+};
+
+use crate::{
+    error::{LoxError, Result},
+    permissions::Permission,
+    value::Value,
+    vm::Vm,
+};
+
+/// Natives for interacting with the process running the script
+pub fn define_natives(vm: &mut Vm) {
+    vm.define_native("getenv", 1, getenv);
+    vm.define_native("setenv", 2, setenv);
+    vm.define_native("exit", 1, exit);
+
+    vm.define_native("readLine", 0, read_line);
+    vm.define_native("readAll", 0, read_all);
+    vm.define_native("readLines", 0, read_lines);
+}
+
+/// Defines `scriptPath`, which is nil in the REPL, and `args`, the command-line arguments after it
+pub fn define_script_globals(vm: &mut Vm, path: Option<&str>, args: Vec<String>) {
+    let path = match path {
+        Some(path) => Value::String(vm.intern(path.to_string())),
+        None => Value::Nil,
+    };
+    vm.define_global("scriptPath", path);
+    let args = vm.new_string_list(args);
+    vm.define_global("args", args);
+}
+
+/// The value of an environment variable, or nil if it isn't set
+fn getenv(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_permission(Permission::Env, None)?;
+    let name = match args[0] {
+        Value::String(name) => name,
+        _ => return vm.runtime_error("Variable name must be a string."),
+    };
+    match env::var(name.as_str()) {
+        Ok(value) => Ok(Value::String(vm.intern(value))),
+        Err(_) => Ok(Value::Nil),
+    }
+}
+
+fn setenv(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_permission(Permission::Env, None)?;
+    let name = match args[0] {
+        Value::String(name)
+            if !name.as_str().is_empty() && !name.as_str().contains(['=', '\0']) =>
+        {
+            name
+        }
+        Value::String(_) => return vm.runtime_error("Invalid environment variable name."),
+        _ => return vm.runtime_error("Variable name must be a string."),
+    };
+    let value = args[1].to_string();
+    if value.contains('\0') {
+        return vm.runtime_error("Environment variable value can't contain a null character.");
+    }
+    env::set_var(name.as_str(), value);
+    Ok(Value::Nil)
+}
+
+/// Stops the script, leaving the embedder to exit the process with the code
+fn exit(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    match args[0] {
+        Value::Int(code) if i32::try_from(code).is_ok() => Err(LoxError::Exit(code as i32)),
+        _ => vm.runtime_error("Exit code must be an integer."),
+    }
+}
+
+/// The next line of stdin without its line ending, or nil at the end of the input. Looping until
+/// nil streams the input a line at a time.
+fn read_line(vm: &mut Vm, _this: Value, _args: &[Value]) -> Result<Value> {
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Value::String(vm.intern(line)))
+        }
+        Err(err) => vm.runtime_error(&format!("Could not read stdin: {}.", err)),
+    }
+}
+
+/// The rest of stdin
+fn read_all(vm: &mut Vm, _this: Value, _args: &[Value]) -> Result<Value> {
+    let mut input = String::new();
+    match io::stdin().lock().read_to_string(&mut input) {
+        Ok(_) => Ok(Value::String(vm.intern(input))),
+        Err(err) => vm.runtime_error(&format!("Could not read stdin: {}.", err)),
+    }
+}
+
+/// The rest of stdin as a list of lines
+fn read_lines(vm: &mut Vm, _this: Value, _args: &[Value]) -> Result<Value> {
+    match io::stdin().lock().lines().collect() {
+        Ok(lines) => Ok(vm.new_string_list(lines)),
+        Err(err) => vm.runtime_error(&format!("Could not read stdin: {}.", err)),
+    }
+}
@@ -7,7 +7,7 @@ use strum::{EnumCount, IntoEnumIterator};
 
 use crate::{
     chunk::Chunk,
-    compiler::{ClassCompiler, Compiler, FunctionType},
+    compiler::{ClassCompiler, Compiler, Destructure, FunctionType, Pattern},
     error::{LoxError, Result},
     gc::{Gc, GcRef},
     obj::Function,
@@ -44,9 +44,40 @@ struct Parser<'source> {
     gc: &'source mut Gc,
     had_error: bool,
     panic_mode: bool,
+    /// Whether the last thing compiled was a bare 'this', which private members must be accessed through
+    receiver_is_this: bool,
     rules: ParseRuleTable<'source>,
 }
 
+/// Tracks which enum variants the cases of a match statement handle
+#[derive(Default)]
+struct EnumCoverage<'source> {
+    enum_name: Option<&'source str>,
+    /// Whether some pattern is neither a variant of `enum_name` nor a catch-all
+    has_other_patterns: bool,
+    has_catch_all: bool,
+    covered: Vec<&'source str>,
+}
+
+impl<'source> EnumCoverage<'source> {
+    fn add_case(&mut self, patterns: &[Pattern<'source>], guarded: bool) {
+        for pattern in patterns {
+            match pattern {
+                Pattern::Wildcard | Pattern::Binding(_) => self.has_catch_all |= !guarded,
+                Pattern::Constant(path) if path.len() == 2 => {
+                    if *self.enum_name.get_or_insert(path[0].lexeme) != path[0].lexeme {
+                        self.has_other_patterns = true;
+                    }
+                    if !guarded {
+                        self.covered.push(path[1].lexeme);
+                    }
+                }
+                _ => self.has_other_patterns = true,
+            }
+        }
+    }
+}
+
 impl<'source> Parser<'source> {
     fn new(scanner: Scanner<'source>, gc: &'source mut Gc) -> Parser<'source> {
         let rules = ParseRuleTable::new();
@@ -60,6 +91,7 @@ impl<'source> Parser<'source> {
             gc,
             had_error: false,
             panic_mode: false,
+            receiver_is_this: false,
             rules,
         }
     }
@@ -118,16 +150,74 @@ impl<'source> Parser<'source> {
         self.push_compiler(function_type);
         self.begin_scope();
 
+        match function_type {
+            // Getters have no parameter list at all
+            FunctionType::Getter => {}
+            FunctionType::Setter => {
+                self.consume(TokenType::Equal, "Expect '=' after setter name.");
+                self.parameter_list();
+            }
+            _ => self.parameter_list(),
+        }
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.");
+        self.block();
+
+        let Compiler { function, .. } = self.pop_compiler();
+        if matches!(function_type, FunctionType::Setter) && function.arity != 1 {
+            self.error_str("A setter must have exactly one parameter.");
+        }
+        let value = Value::Function(self.gc.alloc(function));
+
+        let constant = self.make_constant(value);
+        self.emit(OpCode::Closure(constant));
+    }
+
+    fn parameter_list(&mut self) {
         self.consume(TokenType::LeftParen, "Expect '(' after function name.");
+        let mut destructured = vec![];
         if !self.check(TokenType::RightParen) {
             // Parse function parameters
             loop {
+                if self.compiler.function.variadic {
+                    self.error_at_current("A rest parameter must be the last parameter.");
+                }
+                if self.advance_matching(TokenType::DotDotDot) {
+                    let constant = self.parse_variable("Expect rest parameter name.");
+                    self.define_variable(constant);
+                    self.compiler.function.variadic = true;
+                    if !self.advance_matching(TokenType::Comma) {
+                        break;
+                    }
+                    continue;
+                }
+
                 self.compiler.function.arity += 1;
 
                 if self.compiler.function.arity > 255 {
                     self.error_at_current("Can't have more than 255 parameters.");
                 }
-                let constant = self.parse_variable("Expect parameter name");
+                let constant = if self.advance_matching_destructure() {
+                    // The argument goes in a hidden slot, to be taken apart once the body starts
+                    let pattern = self.destructure_pattern();
+                    self.add_local(Token::none());
+                    destructured.push((self.compiler.last_local_slot(), pattern));
+                    Constant::none()
+                } else {
+                    self.parse_variable("Expect parameter name")
+                };
+                if self.advance_matching(TokenType::Equal) {
+                    // Only runs when the argument is missing, see Function::entry_points
+                    let entry_point = self.current_chunk().code.len();
+                    self.compiler.function.entry_points.push(entry_point);
+                    let slot = self.compiler.last_local_slot();
+                    self.expression();
+                    self.emit(OpCode::SetLocal(slot));
+                    self.emit(OpCode::Pop);
+                } else if self.compiler.function.entry_points.is_empty() {
+                    self.compiler.function.min_arity += 1;
+                } else {
+                    self.error_str("Can't have a required parameter after an optional one.");
+                }
                 self.define_variable(constant);
 
                 if !self.advance_matching(TokenType::Comma) {
@@ -136,28 +226,74 @@ impl<'source> Parser<'source> {
             }
         }
         self.consume(TokenType::RightParen, "Expect ')' after parameters.");
-        self.consume(TokenType::LeftBrace, "Expect '{' before function body.");
-        self.block();
 
-        let Compiler { function, .. } = self.pop_compiler();
-        let value = Value::Function(self.gc.alloc(function));
+        if !self.compiler.function.entry_points.is_empty() {
+            let body = self.current_chunk().code.len();
+            self.compiler.function.entry_points.push(body);
+        }
 
-        let constant = self.make_constant(value);
-        self.emit(OpCode::Closure(constant));
+        for (slot, pattern) in destructured {
+            self.emit(OpCode::GetLocal(slot));
+            self.destructure(&pattern);
+        }
     }
 
     fn method(&mut self) {
+        if self.advance_matching(TokenType::PrivateIdentifier) {
+            let constant = self.identifier_constant(self.previous);
+            if self.check(TokenType::LeftBrace) || self.check(TokenType::Equal) {
+                self.error_at_current("Getters and setters can't be private.");
+            }
+            self.function(FunctionType::Method);
+            self.emit(OpCode::PrivateMethod(constant));
+            return;
+        }
+
         self.consume(TokenType::Identifier, "Expect method name.");
         let constant = self.identifier_constant(self.previous);
 
-        let function_type = if self.previous.lexeme == "init" {
-            FunctionType::Initializer
+        if self.check(TokenType::LeftBrace) {
+            self.function(FunctionType::Getter);
+            self.emit(OpCode::Getter(constant));
+        } else if self.check(TokenType::Equal) {
+            self.function(FunctionType::Setter);
+            self.emit(OpCode::Setter(constant));
         } else {
-            FunctionType::Method
-        };
-        self.function(function_type);
+            let function_type = if self.previous.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.function(function_type);
+            self.emit(OpCode::Method(constant));
+        }
+    }
+
+    /// A `class name() {}` static method or `class var name = value;` static field
+    fn static_member(&mut self) {
+        let is_field = self.advance_matching(TokenType::Var);
+        if self.advance_matching(TokenType::PrivateIdentifier) {
+            self.error_str("Static members can't be private.");
+        } else if is_field {
+            self.consume(TokenType::Identifier, "Expect static field name.");
+        } else {
+            self.consume(TokenType::Identifier, "Expect static method name.");
+        }
+        let constant = self.identifier_constant(self.previous);
 
-        self.emit(OpCode::Method(constant));
+        if is_field {
+            if self.advance_matching(TokenType::Equal) {
+                self.class_compiler.as_mut().unwrap().in_static_field = true;
+                self.expression();
+                self.class_compiler.as_mut().unwrap().in_static_field = false;
+            } else {
+                self.emit(OpCode::Nil);
+            }
+            self.consume(TokenType::Semicolon, "Expect ';' after static field.");
+        } else {
+            self.function(FunctionType::StaticMethod);
+        }
+        self.emit(OpCode::Static(constant));
     }
 
     fn class_declaration(&mut self) {
@@ -194,21 +330,156 @@ impl<'source> Parser<'source> {
             self.class_compiler.as_mut().unwrap().has_superclass = true;
         }
 
+        let traits = self.trait_list();
+        self.class_body(class_name, &traits);
+
+        if self.class_compiler.as_ref().unwrap().has_superclass {
+            self.end_scope();
+        }
+
+        self.class_compiler = self.class_compiler.as_mut().unwrap().enclosing.take();
+    }
+
+    fn trait_declaration(&mut self) {
+        self.consume(TokenType::Identifier, "Expect trait name.");
+        let trait_name = self.previous;
+        let name_constant = self.identifier_constant(self.previous);
+        self.declare_variable();
+
+        self.emit(OpCode::Trait(name_constant));
+        self.define_variable(name_constant);
+
+        let mut class_compiler = ClassCompiler::new(self.class_compiler.take());
+        class_compiler.is_trait = true;
+        self.class_compiler = Some(Box::new(class_compiler));
+
+        let traits = self.trait_list();
+        self.class_body(trait_name, &traits);
+
+        self.class_compiler = self.class_compiler.as_mut().unwrap().enclosing.take();
+    }
+
+    fn enum_declaration(&mut self) {
+        self.consume(TokenType::Identifier, "Expect enum name.");
+        let enum_name = self.previous;
+        let name_constant = self.identifier_constant(self.previous);
+        self.declare_variable();
+
+        self.emit(OpCode::Enum(name_constant));
+        self.define_variable(name_constant);
+
+        // Load the enum back so its variants can be added
+        self.unassignable_named_variable(enum_name);
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before enum body.");
+        let mut variants = vec![];
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof) {
+            self.consume(TokenType::Identifier, "Expect variant name.");
+            let variant = self.previous;
+            if variants.contains(&variant.lexeme) {
+                self.error_str("Already a variant with this name in this enum.");
+            }
+            let name = self.identifier_constant(variant);
+            let field_count = self.variant_fields();
+            self.emit(OpCode::Variant { name, field_count });
+            variants.push(variant.lexeme);
+
+            if !self.advance_matching(TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after enum body.");
+        self.emit(OpCode::Pop);
+
+        self.compiler.declare_enum(enum_name.lexeme, variants);
+    }
+
+    /// The optional `(field: value, ...)` payload of an enum variant, pushing each field's name
+    /// and value
+    fn variant_fields(&mut self) -> u8 {
+        let mut field_count = 0;
+        if !self.advance_matching(TokenType::LeftParen) {
+            return field_count;
+        }
+
+        if !self.check(TokenType::RightParen) {
+            loop {
+                self.consume(TokenType::Identifier, "Expect field name.");
+                if matches!(self.previous.lexeme, "name" | "ordinal") {
+                    self.error_str("Variant fields can't be called 'name' or 'ordinal'.");
+                }
+                let field = self.identifier_constant(self.previous);
+                self.emit(OpCode::Constant(field));
+                self.consume(TokenType::Colon, "Expect ':' after field name.");
+                self.expression();
+
+                if field_count == u8::MAX {
+                    self.error_str("Can't have more than 255 fields.");
+                } else {
+                    field_count += 1;
+                }
+
+                if !self.advance_matching(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after variant fields.");
+        field_count
+    }
+
+    /// The optional `with A, B` clause naming the traits a class or trait includes
+    fn trait_list(&mut self) -> Vec<Token<'source>> {
+        let mut traits = Vec::new();
+        if self.advance_matching(TokenType::With) {
+            loop {
+                self.consume(TokenType::Identifier, "Expect trait name.");
+                if traits.len() == u8::MAX as usize {
+                    self.error_str("Can't include more than 255 traits.");
+                } else {
+                    traits.push(self.previous);
+                }
+
+                if !self.advance_matching(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        traits
+    }
+
+    fn class_body(&mut self, class_name: Token, traits: &[Token]) {
+        // Keep the class in a hidden local while the body is compiled. Static field initializers use
+        // it as their 'this', and methods capture it to find the class owning their private members.
+        self.begin_scope();
         self.unassignable_named_variable(class_name);
+        self.add_local(Token::class());
+        self.define_variable(Constant::none());
 
+        let is_trait = self.class_compiler.as_ref().unwrap().is_trait;
         self.consume(TokenType::LeftBrace, "Expect '{' before class body");
         while !self.check(TokenType::RightBrace) && !self.check(TokenType::Eof) {
-            // Lox doesn't have field declarations -> everything between the braces must be a method
-            self.method();
+            if self.advance_matching(TokenType::Class) {
+                if is_trait {
+                    self.error_str("Traits can't have static members.");
+                }
+                self.static_member();
+            } else {
+                self.method();
+            }
         }
         self.consume(TokenType::RightBrace, "Expect '}' after class body");
-        self.emit(OpCode::Pop);
 
-        if self.class_compiler.as_ref().unwrap().has_superclass {
-            self.end_scope();
+        // Traits are included once the body is defined, so the class's own methods take precedence
+        if !traits.is_empty() {
+            for name in traits {
+                self.unassignable_named_variable(*name);
+            }
+            self.emit(OpCode::Include {
+                trait_count: traits.len() as u8,
+            });
         }
-
-        self.class_compiler = self.class_compiler.as_mut().unwrap().enclosing.take();
+        self.end_scope();
     }
 
     fn fun_declaration(&mut self) {
@@ -219,6 +490,18 @@ impl<'source> Parser<'source> {
     }
 
     fn var_declaration(&mut self) {
+        if self.advance_matching_destructure() {
+            let pattern = self.destructure_pattern();
+            self.consume(TokenType::Equal, "Expect '=' after destructuring pattern.");
+            self.expression();
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after variable declaration",
+            );
+            self.destructure(&pattern);
+            return;
+        }
+
         let global = self.parse_variable("Expect variable name.");
 
         if self.advance_matching(TokenType::Equal) {
@@ -234,13 +517,189 @@ impl<'source> Parser<'source> {
         self.define_variable(global);
     }
 
+    fn const_declaration(&mut self) {
+        let global = self.parse_variable("Expect constant name.");
+
+        self.consume(TokenType::Equal, "Expect '=' after constant name.");
+        self.expression();
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after constant declaration",
+        );
+
+        if self.compiler.is_local_scope() {
+            self.compiler.mark_var_const();
+            self.define_variable(global);
+        } else {
+            self.emit(OpCode::DefineConstGlobal(global));
+        }
+    }
+
+    /// Consumes the '(' or '{' starting a destructuring pattern
+    fn advance_matching_destructure(&mut self) -> bool {
+        self.advance_matching(TokenType::LeftParen) || self.advance_matching(TokenType::LeftBrace)
+    }
+
+    /// Parses the rest of a destructuring pattern, after its opening '(' or '{'
+    fn destructure_pattern(&mut self) -> Destructure<'source> {
+        let is_tuple = matches!(self.previous.token_type, TokenType::LeftParen);
+        let mut targets = vec![];
+        loop {
+            self.consume(TokenType::Identifier, "Expect variable name.");
+            let field = self.previous;
+            if !is_tuple && self.advance_matching(TokenType::Colon) {
+                self.consume(TokenType::Identifier, "Expect variable name after ':'.");
+            }
+            if targets.len() == u8::MAX as usize {
+                self.error_str("Can't destructure more than 255 values.");
+            } else {
+                targets.push((field, self.previous));
+            }
+
+            if !self.advance_matching(TokenType::Comma) {
+                break;
+            }
+        }
+
+        if is_tuple {
+            self.consume(
+                TokenType::RightParen,
+                "Expect ')' after destructuring pattern.",
+            );
+            Destructure::Tuple(targets.into_iter().map(|(name, _)| name).collect())
+        } else {
+            self.consume(
+                TokenType::RightBrace,
+                "Expect '}' after destructuring pattern.",
+            );
+            Destructure::Fields(targets)
+        }
+    }
+
+    /// Pops the value on top of the stack, declaring a variable for each part the pattern takes
+    fn destructure(&mut self, pattern: &Destructure<'source>) {
+        match pattern {
+            Destructure::Tuple(names) => {
+                self.emit(OpCode::Unpack {
+                    item_count: names.len() as u8,
+                });
+                if self.compiler.is_local_scope() {
+                    // The items are already in the right stack slots
+                    for name in names {
+                        self.declare_named_variable(*name);
+                        self.compiler.mark_var_initialized();
+                    }
+                } else {
+                    for name in names.iter().rev() {
+                        let global = self.identifier_constant(*name);
+                        self.emit(OpCode::DefineGlobal(global));
+                    }
+                }
+            }
+            Destructure::Fields(fields) => {
+                if self.compiler.is_local_scope() {
+                    // Keep the value in a hidden local while its fields are copied out
+                    self.add_local(Token::none());
+                    self.compiler.mark_var_initialized();
+                    let slot = self.compiler.last_local_slot();
+                    for (field, name) in fields {
+                        self.emit(OpCode::GetLocal(slot));
+                        let field = self.identifier_constant(*field);
+                        self.emit(OpCode::GetProperty(field));
+                        self.declare_named_variable(*name);
+                        self.compiler.mark_var_initialized();
+                    }
+                } else {
+                    for (field, name) in fields {
+                        self.emit(OpCode::Dup);
+                        let field = self.identifier_constant(*field);
+                        self.emit(OpCode::GetProperty(field));
+                        let global = self.identifier_constant(*name);
+                        self.emit(OpCode::DefineGlobal(global));
+                    }
+                    self.emit(OpCode::Pop);
+                }
+            }
+        }
+    }
+
+    /// Whether the tokens ahead are a destructuring pattern followed by '=', which would
+    /// otherwise parse as a grouping or a block. Like a tuple, a parenthesised pattern needs a
+    /// comma, so `(a) = 5;` is still an invalid assignment target.
+    fn is_destructuring_assignment(&self) -> bool {
+        let close = match self.current.token_type {
+            TokenType::LeftParen => TokenType::RightParen,
+            TokenType::LeftBrace => TokenType::RightBrace,
+            _ => return false,
+        };
+        let mut scanner = self.scanner.clone();
+        let mut has_comma = false;
+        loop {
+            if scanner.scan_token().token_type != TokenType::Identifier {
+                return false;
+            }
+            let mut next = scanner.scan_token().token_type;
+            if close == TokenType::RightBrace && next == TokenType::Colon {
+                if scanner.scan_token().token_type != TokenType::Identifier {
+                    return false;
+                }
+                next = scanner.scan_token().token_type;
+            }
+            if next == close {
+                return (has_comma || close == TokenType::RightBrace)
+                    && scanner.scan_token().token_type == TokenType::Equal;
+            }
+            if next != TokenType::Comma {
+                return false;
+            }
+            has_comma = true;
+        }
+    }
+
+    /// `(a, b) = list;` or `{x, y} = instance;`
+    fn destructuring_assignment(&mut self) {
+        self.advance();
+        let pattern = self.destructure_pattern();
+        self.consume(TokenType::Equal, "Expect '=' after destructuring pattern.");
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after assignment.");
+
+        match pattern {
+            Destructure::Tuple(names) => {
+                self.emit(OpCode::Unpack {
+                    item_count: names.len() as u8,
+                });
+                for name in names.into_iter().rev() {
+                    self.set_named_variable(name);
+                    self.emit(OpCode::Pop);
+                }
+            }
+            Destructure::Fields(fields) => {
+                for (field, name) in fields {
+                    self.emit(OpCode::Dup);
+                    let field = self.identifier_constant(field);
+                    self.emit(OpCode::GetProperty(field));
+                    self.set_named_variable(name);
+                    self.emit(OpCode::Pop);
+                }
+                self.emit(OpCode::Pop);
+            }
+        }
+    }
+
     fn declaration(&mut self) {
         if self.advance_matching(TokenType::Class) {
             self.class_declaration();
+        } else if self.advance_matching(TokenType::Trait) {
+            self.trait_declaration();
+        } else if self.advance_matching(TokenType::Enum) {
+            self.enum_declaration();
         } else if self.advance_matching(TokenType::Fun) {
             self.fun_declaration();
         } else if self.advance_matching(TokenType::Var) {
             self.var_declaration();
+        } else if self.advance_matching(TokenType::Const) {
+            self.const_declaration();
         } else {
             self.statement();
         }
@@ -253,6 +712,8 @@ impl<'source> Parser<'source> {
     fn statement(&mut self) {
         if self.advance_matching(TokenType::Print) {
             self.print_statement();
+        } else if self.is_destructuring_assignment() {
+            self.destructuring_assignment();
         } else if self.advance_matching(TokenType::LeftBrace) {
             self.begin_scope();
             self.block();
@@ -265,6 +726,8 @@ impl<'source> Parser<'source> {
             self.for_statement();
         } else if self.advance_matching(TokenType::Return) {
             self.return_statement();
+        } else if self.advance_matching(TokenType::Match) {
+            self.match_statement();
         } else {
             self.expression_statement();
         }
@@ -321,6 +784,8 @@ impl<'source> Parser<'source> {
             // No initializer
         } else if self.advance_matching(TokenType::Var) {
             self.var_declaration();
+        } else if self.advance_matching(TokenType::Const) {
+            self.const_declaration();
         } else {
             self.expression_statement();
         }
@@ -369,6 +834,279 @@ impl<'source> Parser<'source> {
         self.end_scope();
     }
 
+    fn match_statement(&mut self) {
+        self.begin_scope();
+
+        // Store the subject in a hidden local so it's only evaluated once
+        self.consume(TokenType::LeftParen, "Expect '(' after 'match'.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after match subject.");
+        let subject = Token {
+            token_type: TokenType::Match,
+            lexeme: "match",
+            line: self.previous.line,
+        };
+        self.add_local(subject);
+        self.compiler.mark_var_initialized();
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before match cases.");
+        let mut end_jumps = vec![];
+        let mut coverage = EnumCoverage::default();
+        while self.advance_matching(TokenType::Case) {
+            let mut patterns = vec![self.pattern()];
+            while self.advance_matching(TokenType::Comma) {
+                patterns.push(self.pattern());
+            }
+            if patterns.len() > 1 && patterns.iter().any(Pattern::has_bindings) {
+                self.error_str("Can't bind variables in a case with several patterns.");
+            }
+
+            // Test each alternative in turn, leaving whether any matched on the stack
+            let mut matched_jumps = vec![];
+            for (i, pattern) in patterns.iter().enumerate() {
+                self.pattern_test(pattern, subject, &mut vec![]);
+                if i < patterns.len() - 1 {
+                    let next_jump = self.emit_jump(OpCode::JumpIfFalse(Jump::none()));
+                    matched_jumps.push(self.emit_jump(OpCode::Jump(Jump::none())));
+                    self.patch_jump(next_jump);
+                    self.emit(OpCode::Pop);
+                }
+            }
+            for matched_jump in matched_jumps {
+                self.patch_jump(matched_jump);
+            }
+            let case_jump = self.emit_jump(OpCode::JumpIfFalse(Jump::none()));
+            self.emit(OpCode::Pop);
+
+            // Each case gets its own scope for its bindings
+            self.begin_scope();
+            self.pattern_bindings(&patterns[0], subject, &mut vec![]);
+
+            let guard_jump = if self.advance_matching(TokenType::If) {
+                self.expression();
+                let guard_jump = self.emit_jump(OpCode::JumpIfFalse(Jump::none()));
+                self.emit(OpCode::Pop);
+                Some(guard_jump)
+            } else {
+                None
+            };
+
+            coverage.add_case(&patterns, guard_jump.is_some());
+
+            self.consume(TokenType::FatArrow, "Expect '=>' after case pattern.");
+            self.statement();
+
+            let binding_captures = self.compiler.scope_local_captures();
+            self.end_scope();
+            end_jumps.push(self.emit_jump(OpCode::Jump(Jump::none())));
+
+            // A failed guard discards the guard's result and the bindings before trying the next case
+            let guard_fail_jump = guard_jump.map(|guard_jump| {
+                self.patch_jump(guard_jump);
+                self.emit(OpCode::Pop);
+                for is_captured in binding_captures {
+                    self.emit(if is_captured {
+                        OpCode::CloseUpvalue
+                    } else {
+                        OpCode::Pop
+                    });
+                }
+                self.emit_jump(OpCode::Jump(Jump::none()))
+            });
+
+            self.patch_jump(case_jump);
+            self.emit(OpCode::Pop);
+            if let Some(guard_fail_jump) = guard_fail_jump {
+                self.patch_jump(guard_fail_jump);
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after match cases.");
+        self.check_enum_coverage(&coverage);
+
+        for end_jump in end_jumps {
+            self.patch_jump(end_jump);
+        }
+        self.end_scope();
+    }
+
+    /// Report a match whose cases are all variants of one enum but leave some out
+    fn check_enum_coverage(&mut self, coverage: &EnumCoverage<'source>) {
+        let enum_name = match coverage.enum_name {
+            Some(name) if !coverage.has_other_patterns && !coverage.has_catch_all => name,
+            _ => return,
+        };
+        // The enum may not be declared in this script, or not be in scope
+        let missing: Vec<_> = match self.compiler.resolve_enum(enum_name) {
+            Some(variants) => variants
+                .iter()
+                .filter(|variant| !coverage.covered.contains(variant))
+                .copied()
+                .collect(),
+            None => return,
+        };
+        if !missing.is_empty() {
+            // The parser is still in step with the source, so carry on without synchronizing
+            self.error_without_sync(&format!(
+                "Match on '{}' doesn't cover {}.",
+                enum_name,
+                missing.join(", ")
+            ));
+        }
+    }
+
+    fn pattern(&mut self) -> Pattern<'source> {
+        if self.advance_matching(TokenType::Identifier) {
+            let name = self.previous;
+            if self.advance_matching(TokenType::LeftBrace) {
+                let mut fields = vec![];
+                if !self.check(TokenType::RightBrace) {
+                    loop {
+                        self.consume(TokenType::Identifier, "Expect field name.");
+                        let field = self.previous;
+                        let pattern = if self.advance_matching(TokenType::Colon) {
+                            self.pattern()
+                        } else {
+                            Pattern::Binding(field)
+                        };
+                        fields.push((field, pattern));
+
+                        if !self.advance_matching(TokenType::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(TokenType::RightBrace, "Expect '}' after field patterns.");
+                Pattern::Instance {
+                    class: name,
+                    fields,
+                }
+            } else if self.check(TokenType::Dot) {
+                let mut path = vec![name];
+                while self.advance_matching(TokenType::Dot) {
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.");
+                    path.push(self.previous);
+                }
+                Pattern::Constant(path)
+            } else if name.lexeme == "_" {
+                Pattern::Wildcard
+            } else {
+                Pattern::Binding(name)
+            }
+        } else {
+            let negate = self.advance_matching(TokenType::Minus);
+            self.advance();
+            let value = match self.previous.token_type {
+                TokenType::Number => self.previous.lexeme.parse().map(Value::Number).ok(),
+                TokenType::Integer => self.previous.lexeme.parse().map(Value::Int).ok(),
+                TokenType::String if !negate => {
+                    let string = &self.previous.lexeme[1..self.previous.lexeme.len() - 1];
+                    Some(Value::String(self.gc.intern(string.to_string())))
+                }
+                TokenType::True if !negate => Some(Value::Bool(true)),
+                TokenType::False if !negate => Some(Value::Bool(false)),
+                TokenType::Nil if !negate => Some(Value::Nil),
+                _ => None,
+            };
+            match (value, negate) {
+                (Some(Value::Number(x)), true) => Pattern::Literal(Value::Number(-x)),
+                (Some(Value::Int(x)), true) => Pattern::Literal(Value::Int(-x)),
+                (Some(value), _) => Pattern::Literal(value),
+                (None, _) => {
+                    self.error_str("Expect pattern.");
+                    Pattern::Wildcard
+                }
+            }
+        }
+    }
+
+    /// Push the part of the match subject reached by following the fields in `path`
+    fn load_match_path(&mut self, subject: Token, path: &[Token]) {
+        self.unassignable_named_variable(subject);
+        for field in path {
+            let name = self.identifier_constant(*field);
+            self.emit(OpCode::GetProperty(name));
+        }
+    }
+
+    /// Emit code that pushes whether the value at `path` matches the pattern
+    fn pattern_test(
+        &mut self,
+        pattern: &Pattern<'source>,
+        subject: Token,
+        path: &mut Vec<Token<'source>>,
+    ) {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => self.emit(OpCode::True),
+            Pattern::Literal(value) => {
+                self.load_match_path(subject, path);
+                self.emit_constant(*value);
+                self.emit(OpCode::Equal);
+            }
+            Pattern::Constant(constant) => {
+                self.load_match_path(subject, path);
+                self.unassignable_named_variable(constant[0]);
+                for name in &constant[1..] {
+                    let name = self.identifier_constant(*name);
+                    self.emit(OpCode::GetProperty(name));
+                }
+                self.emit(OpCode::Equal);
+            }
+            Pattern::Instance { class, fields } => {
+                self.load_match_path(subject, path);
+                self.unassignable_named_variable(*class);
+                self.emit(OpCode::IsInstance);
+
+                // Every test jumps to the end as soon as one fails, leaving false as the result
+                let mut fail_jumps = vec![];
+                for (field, pattern) in fields {
+                    fail_jumps.push(self.emit_jump(OpCode::JumpIfFalse(Jump::none())));
+                    self.emit(OpCode::Pop);
+                    self.load_match_path(subject, path);
+                    let name = self.identifier_constant(*field);
+                    self.emit(OpCode::HasField(name));
+
+                    if !matches!(pattern, Pattern::Wildcard | Pattern::Binding(_)) {
+                        fail_jumps.push(self.emit_jump(OpCode::JumpIfFalse(Jump::none())));
+                        self.emit(OpCode::Pop);
+                        path.push(*field);
+                        self.pattern_test(pattern, subject, path);
+                        path.pop();
+                    }
+                }
+                for fail_jump in fail_jumps {
+                    self.patch_jump(fail_jump);
+                }
+            }
+        }
+    }
+
+    /// Declare a local for each name bound by a pattern that is known to match
+    fn pattern_bindings(
+        &mut self,
+        pattern: &Pattern<'source>,
+        subject: Token,
+        path: &mut Vec<Token<'source>>,
+    ) {
+        match pattern {
+            Pattern::Binding(name) => {
+                if self.compiler.is_local_already_in_scope(*name) {
+                    self.error_str("Already a variable with this name in this scope.");
+                }
+                self.load_match_path(subject, path);
+                self.add_local(*name);
+                self.compiler.mark_var_initialized();
+            }
+            Pattern::Instance { fields, .. } => {
+                for (field, pattern) in fields {
+                    path.push(*field);
+                    self.pattern_bindings(pattern, subject, path);
+                    path.pop();
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Constant(_) => {}
+        }
+    }
+
     fn expression_statement(&mut self) {
         self.expression();
         self.consume(TokenType::Semicolon, "Expect ';' after expression.");
@@ -389,8 +1127,12 @@ impl<'source> Parser<'source> {
         if self.advance_matching(TokenType::Semicolon) {
             self.emit_return();
         } else {
-            if let FunctionType::Initializer = self.compiler.function_type {
-                self.error_str("Can't return a value from an initializer.")
+            match self.compiler.function_type {
+                FunctionType::Initializer => {
+                    self.error_str("Can't return a value from an initializer.")
+                }
+                FunctionType::Setter => self.error_str("Can't return a value from a setter."),
+                _ => {}
             }
             self.expression();
             self.consume(TokenType::Semicolon, "Expect ';' after return value.");
@@ -408,12 +1150,16 @@ impl<'source> Parser<'source> {
             }
             match self.current.token_type {
                 TokenType::Class
+                | TokenType::Trait
+                | TokenType::Enum
                 | TokenType::Fun
                 | TokenType::Var
+                | TokenType::Const
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
+                | TokenType::Match
                 | TokenType::Return => return,
                 _ => {
                     // Do nothing
@@ -429,8 +1175,28 @@ impl<'source> Parser<'source> {
         self.emit_constant(Value::Number(value))
     }
 
+    fn integer(&mut self, _can_assign: bool) {
+        match self.previous.lexeme.parse() {
+            Ok(value) => self.emit_constant(Value::Int(value)),
+            Err(_) => self.error_str("Integer literal is too large."),
+        }
+    }
+
     fn grouping(&mut self, _can_assign: bool) {
         self.expression();
+        if self.check(TokenType::Comma) {
+            // A tuple like `(a, b)`, which is a list of a fixed size
+            let mut item_count = 1;
+            while self.advance_matching(TokenType::Comma) {
+                self.expression();
+                if item_count == u8::MAX {
+                    self.error_str("Can't have more than 255 items in a tuple.");
+                } else {
+                    item_count += 1;
+                }
+            }
+            self.emit(OpCode::List { item_count });
+        }
         self.consume(TokenType::RightParen, "Expect ')' after expression.");
     }
 
@@ -439,13 +1205,65 @@ impl<'source> Parser<'source> {
         self.emit(OpCode::Call { arg_count });
     }
 
+    fn list(&mut self, _can_assign: bool) {
+        let mut item_count: u8 = 0;
+
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                self.expression();
+                if item_count == u8::MAX {
+                    self.error_str("Can't have more than 255 items in a list literal.");
+                } else {
+                    item_count += 1;
+                }
+
+                if !self.advance_matching(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list items.");
+        self.emit(OpCode::List { item_count });
+    }
+
+    fn subscript(&mut self, can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightBracket, "Expect ']' after index.");
+
+        if can_assign && self.advance_matching(TokenType::Equal) {
+            self.expression();
+            self.emit(OpCode::SetIndex);
+        } else if let Some(operator) = self.match_compound_assignment(can_assign) {
+            // Keep a copy of the list and index around for the SetIndex
+            self.emit(OpCode::DupPair);
+            self.emit(OpCode::GetIndex);
+            self.expression();
+            self.emit(operator);
+            self.emit(OpCode::SetIndex);
+        } else {
+            self.emit(OpCode::GetIndex);
+        }
+    }
+
     fn dot(&mut self, can_assign: bool) {
+        if self.advance_matching(TokenType::PrivateIdentifier) {
+            self.private_member(can_assign);
+            return;
+        }
+
         self.consume(TokenType::Identifier, "Expect property name after '.'.");
         let name = self.identifier_constant(self.previous);
 
         if can_assign && self.advance_matching(TokenType::Equal) {
             self.expression();
             self.emit(OpCode::SetProperty(name));
+        } else if let Some(operator) = self.match_compound_assignment(can_assign) {
+            // Keep a copy of the receiver around for the SetProperty
+            self.emit(OpCode::Dup);
+            self.emit(OpCode::GetProperty(name));
+            self.expression();
+            self.emit(operator);
+            self.emit(OpCode::SetProperty(name));
         } else if self.advance_matching(TokenType::LeftParen) {
             let arg_count = self.argument_list();
             self.emit(OpCode::Invoke(Invoke { name, arg_count }));
@@ -454,6 +1272,39 @@ impl<'source> Parser<'source> {
         }
     }
 
+    /// `this.#name`, which is looked up among the members private to the enclosing class
+    fn private_member(&mut self, can_assign: bool) {
+        if self.class_compiler.is_none() {
+            self.error_str("Can't use a private member outside of a class.");
+        } else if !self.receiver_is_this {
+            self.error_str("Private members can only be accessed through 'this'.");
+        } else if self.in_static_context() {
+            self.error_str("Can't use private members in a static context.");
+        }
+        let name = self.identifier_constant(self.previous);
+
+        if can_assign && self.advance_matching(TokenType::Equal) {
+            self.expression();
+            self.unassignable_named_variable(Token::class());
+            self.emit(OpCode::SetPrivate(name));
+        } else if let Some(operator) = self.match_compound_assignment(can_assign) {
+            self.emit(OpCode::Dup);
+            self.unassignable_named_variable(Token::class());
+            self.emit(OpCode::GetPrivate(name));
+            self.expression();
+            self.emit(operator);
+            self.unassignable_named_variable(Token::class());
+            self.emit(OpCode::SetPrivate(name));
+        } else if self.advance_matching(TokenType::LeftParen) {
+            let arg_count = self.argument_list();
+            self.unassignable_named_variable(Token::class());
+            self.emit(OpCode::InvokePrivate(Invoke { name, arg_count }));
+        } else {
+            self.unassignable_named_variable(Token::class());
+            self.emit(OpCode::GetPrivate(name));
+        }
+    }
+
     fn unary(&mut self, _can_assign: bool) {
         let operator_type = self.previous.token_type;
 
@@ -464,6 +1315,7 @@ impl<'source> Parser<'source> {
         match operator_type {
             TokenType::Minus => self.emit(OpCode::Negate),
             TokenType::Bang => self.emit(OpCode::Not),
+            TokenType::Tilde => self.emit(OpCode::BitNot),
             _ => unreachable!(),
         }
     }
@@ -473,8 +1325,13 @@ impl<'source> Parser<'source> {
         let operator_type = self.previous.token_type;
 
         // Compile the right operand
-        // Each binary operator's right-hand operand precedence is one level higher than its own
-        self.parse_precedence(self.get_rule(operator_type).precedence.next());
+        // Each binary operator's right-hand operand precedence is one level higher than its own,
+        // except for the right-associative '**' whose operand may be another exponent or a unary
+        if operator_type == TokenType::StarStar {
+            self.parse_precedence(Precedence::Unary);
+        } else {
+            self.parse_precedence(self.get_rule(operator_type).precedence.next());
+        }
 
         // Compile the operator
         match operator_type {
@@ -482,9 +1339,18 @@ impl<'source> Parser<'source> {
             TokenType::Minus => self.emit(OpCode::Subtract),
             TokenType::Star => self.emit(OpCode::Multiply),
             TokenType::Slash => self.emit(OpCode::Divide),
+            TokenType::TildeSlash => self.emit(OpCode::IntDivide),
+            TokenType::Percent => self.emit(OpCode::Modulo),
+            TokenType::StarStar => self.emit(OpCode::Power),
+            TokenType::Ampersand => self.emit(OpCode::BitAnd),
+            TokenType::Pipe => self.emit(OpCode::BitOr),
+            TokenType::Caret => self.emit(OpCode::BitXor),
+            TokenType::LessLess => self.emit(OpCode::ShiftLeft),
+            TokenType::GreaterGreater => self.emit(OpCode::ShiftRight),
             TokenType::EqualEqual => self.emit(OpCode::Equal),
             TokenType::Greater => self.emit(OpCode::Greater),
             TokenType::Less => self.emit(OpCode::Less),
+            TokenType::Is => self.emit(OpCode::IsInstance),
             TokenType::BangEqual => {
                 self.emit(OpCode::Equal);
                 self.emit(OpCode::Not);
@@ -527,6 +1393,56 @@ impl<'source> Parser<'source> {
         self.patch_jump(end_jump);
     }
 
+    fn conditional(&mut self, _can_assign: bool) {
+        // Here, the condition has already been compiled
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse(Jump::none()));
+        self.emit(OpCode::Pop); // Condition
+        self.parse_precedence(Precedence::Conditional);
+        let end_jump = self.emit_jump(OpCode::Jump(Jump::none()));
+
+        self.patch_jump(else_jump);
+        self.emit(OpCode::Pop); // Condition
+        self.consume(
+            TokenType::Colon,
+            "Expect ':' after then branch of conditional expression.",
+        );
+        // Parsing at the same precedence makes the operator right-associative
+        self.parse_precedence(Precedence::Conditional);
+        self.patch_jump(end_jump);
+    }
+
+    fn coalesce(&mut self, _can_assign: bool) {
+        // Here, the left operand expression has already been compiled
+        let end_jump = self.emit_jump(OpCode::JumpIfNotNil(Jump::none()));
+
+        self.emit(OpCode::Pop);
+        self.parse_precedence(Precedence::Coalesce);
+        self.patch_jump(end_jump);
+    }
+
+    fn optional_chain(&mut self, _can_assign: bool) {
+        // When the receiver is nil, skip the rest of the chain and leave the nil as its result
+        let nil_jump = self.emit_jump(OpCode::JumpIfNil(Jump::none()));
+
+        if self.advance_matching(TokenType::LeftParen) {
+            self.call(false);
+        } else {
+            self.dot(false);
+        }
+
+        // An optional chain can't be assigned to, so pass can_assign as false
+        while matches!(
+            self.current.token_type,
+            TokenType::Dot | TokenType::LeftParen | TokenType::LeftBracket | TokenType::QuestionDot
+        ) {
+            self.advance();
+            let infix_rule = self.get_rule(self.previous.token_type).infix.unwrap();
+            infix_rule(self, false);
+        }
+
+        self.patch_jump(nil_jump);
+    }
+
     fn literal(&mut self, _can_assign: bool) {
         match self.previous.token_type {
             TokenType::False => self.emit(OpCode::False),
@@ -553,12 +1469,26 @@ impl<'source> Parser<'source> {
             self.error_str("Can't use 'this' outside of a class.");
             return;
         }
-        self.variable(false)
+        self.load_this();
+        self.receiver_is_this = true;
+    }
+
+    fn load_this(&mut self) {
+        let in_static_field = self
+            .class_compiler
+            .as_ref()
+            .is_some_and(|class_compiler| class_compiler.in_static_field);
+        // A static field initializer isn't a method, so its 'this' is the class being declared
+        if in_static_field {
+            self.unassignable_named_variable(Token::class());
+        } else {
+            self.unassignable_named_variable(Token::this());
+        }
     }
 
-    fn super_(&mut self, _can_assign: bool) {
+    fn super_(&mut self, can_assign: bool) {
         if let Some(class_compiler) = &self.class_compiler {
-            if !class_compiler.has_superclass {
+            if !class_compiler.has_superclass && !class_compiler.is_trait {
                 self.error_str("Can't use 'super' in a class with no superclass.");
             }
         } else {
@@ -569,38 +1499,140 @@ impl<'source> Parser<'source> {
         self.consume(TokenType::Identifier, "Expect superclass method name.");
         let name = self.identifier_constant(self.previous);
 
-        self.unassignable_named_variable(Token::this());
-        if self.advance_matching(TokenType::LeftParen) {
+        let is_static = self.in_static_context();
+        self.load_this();
+        if can_assign && self.advance_matching(TokenType::Equal) {
+            if is_static {
+                self.error_str("Can't assign through 'super' in a static context.");
+            }
+            self.expression();
+            self.load_super();
+            self.emit(OpCode::SetSuper(name));
+        } else if self.advance_matching(TokenType::LeftParen) {
             let arg_count = self.argument_list();
-            self.unassignable_named_variable(Token::super_());
-            self.emit(OpCode::SuperInvoke(Invoke { name, arg_count }));
+            self.load_super();
+            let invoke = Invoke { name, arg_count };
+            if is_static {
+                self.emit(OpCode::SuperInvokeStatic(invoke));
+            } else {
+                self.emit(OpCode::SuperInvoke(invoke));
+            }
+        } else {
+            self.load_super();
+            if is_static {
+                self.emit(OpCode::GetSuperStatic(name));
+            } else {
+                self.emit(OpCode::GetSuper(name));
+            }
+        }
+    }
+
+    fn load_super(&mut self) {
+        let is_trait = self
+            .class_compiler
+            .as_ref()
+            .is_some_and(|class_compiler| class_compiler.is_trait);
+        // A trait method's superclass depends on which class included it
+        if is_trait {
+            self.emit(OpCode::HomeSuperclass);
         } else {
             self.unassignable_named_variable(Token::super_());
-            self.emit(OpCode::GetSuper(name));
         }
     }
 
-    fn named_variable(&mut self, name: Token, can_assign: bool) -> Result<()> {
-        let (get_opcode, set_opcode) = {
-            if let Some(index) = self.compiler.resolve_local(name)? {
+    /// Is `this` the class itself, i.e. are we in a static method or static field initializer?
+    fn in_static_context(&self) -> bool {
+        if self
+            .class_compiler
+            .as_ref()
+            .is_some_and(|class_compiler| class_compiler.in_static_field)
+        {
+            return true;
+        }
+
+        // Functions nested in a method share its 'this'
+        let mut compiler = &self.compiler;
+        while let FunctionType::Function = compiler.function_type {
+            match &compiler.enclosing {
+                Some(enclosing) => compiler = enclosing,
+                None => return false,
+            }
+        }
+        matches!(compiler.function_type, FunctionType::StaticMethod)
+    }
+
+    /// The opcodes to read and write the variable with the given name
+    fn resolve_variable(&mut self, name: Token, assigning: bool) -> Result<(OpCode, OpCode)> {
+        Ok(
+            if let Some(index) = self.compiler.resolve_local(name, assigning)? {
                 (OpCode::GetLocal(index), OpCode::SetLocal(index))
-            } else if let Some(index) = self.compiler.resolve_upvalue(name)? {
+            } else if let Some(index) = self.compiler.resolve_upvalue(name, assigning)? {
                 (OpCode::GetUpvalue(index), OpCode::SetUpvalue(index))
             } else {
                 let constant = self.identifier_constant(name);
                 (OpCode::GetGlobal(constant), OpCode::SetGlobal(constant))
-            }
-        };
+            },
+        )
+    }
+
+    fn named_variable(&mut self, name: Token, can_assign: bool) -> Result<()> {
+        let assigning = can_assign
+            && (self.check(TokenType::Equal)
+                || Self::compound_operator(self.current.token_type).is_some());
+        let (get_opcode, set_opcode) = self.resolve_variable(name, assigning)?;
 
         if can_assign && self.advance_matching(TokenType::Equal) {
             self.expression();
             self.emit(set_opcode);
+        } else if let Some(operator) = self.match_compound_assignment(can_assign) {
+            self.emit(get_opcode);
+            self.expression();
+            self.emit(operator);
+            self.emit(set_opcode);
         } else {
             self.emit(get_opcode);
         }
         Ok(())
     }
 
+    /// Consumes a compound assignment operator like '+=', returning the opcode which combines the
+    /// target's current value with the right-hand side
+    fn match_compound_assignment(&mut self, can_assign: bool) -> Option<OpCode> {
+        if !can_assign {
+            return None;
+        }
+        let operator = Self::compound_operator(self.current.token_type)?;
+        self.advance();
+        Some(operator)
+    }
+
+    fn compound_operator(token_type: TokenType) -> Option<OpCode> {
+        Some(match token_type {
+            TokenType::PlusEqual => OpCode::Add,
+            TokenType::MinusEqual => OpCode::Subtract,
+            TokenType::StarEqual => OpCode::Multiply,
+            TokenType::SlashEqual => OpCode::Divide,
+            TokenType::TildeSlashEqual => OpCode::IntDivide,
+            TokenType::PercentEqual => OpCode::Modulo,
+            TokenType::StarStarEqual => OpCode::Power,
+            TokenType::AmpersandEqual => OpCode::BitAnd,
+            TokenType::PipeEqual => OpCode::BitOr,
+            TokenType::CaretEqual => OpCode::BitXor,
+            TokenType::LessLessEqual => OpCode::ShiftLeft,
+            TokenType::GreaterGreaterEqual => OpCode::ShiftRight,
+            _ => return None,
+        })
+    }
+
+    /// Assign the value on top of the stack to the variable, leaving it there
+    fn set_named_variable(&mut self, name: Token) {
+        match self.resolve_variable(name, true) {
+            Ok((_, set_opcode)) => self.emit(set_opcode),
+            Err(LoxError::CompileError(message)) => self.error_at(name, message),
+            Err(_) => unreachable!(),
+        }
+    }
+
     fn unassignable_named_variable(&mut self, name: Token) {
         if let Err(err) = self.named_variable(name, false) {
             self.error(err);
@@ -626,7 +1658,10 @@ impl<'source> Parser<'source> {
             infix_rule(self, can_assign);
         }
 
-        if can_assign && self.advance_matching(TokenType::Equal) {
+        if can_assign
+            && (self.advance_matching(TokenType::Equal)
+                || self.match_compound_assignment(can_assign).is_some())
+        {
             self.error_str("Invalid assignment target.")
         }
     }
@@ -653,14 +1688,17 @@ impl<'source> Parser<'source> {
     }
 
     fn declare_variable(&mut self) {
+        self.declare_named_variable(self.previous);
+    }
+
+    fn declare_named_variable(&mut self, name: Token<'source>) {
         if !self.compiler.is_local_scope() {
+            self.compiler.hide_enum(name.lexeme);
             return;
         }
 
-        let name = self.previous;
-
         if self.compiler.is_local_already_in_scope(name) {
-            self.error_str("Already a variable with this name in this scope.");
+            self.error_at(name, "Already a variable with this name in this scope.");
         }
 
         self.add_local(name);
@@ -708,7 +1746,7 @@ impl<'source> Parser<'source> {
         self.compiler.enclosing = Some(old_compiler);
     }
 
-    fn pop_compiler(&mut self) -> Compiler {
+    fn pop_compiler(&mut self) -> Compiler<'_> {
         self.emit_return();
 
         #[cfg(feature = "debug_print_code")]
@@ -756,6 +1794,7 @@ impl<'source> Parser<'source> {
     }
 
     fn emit(&mut self, opcode: OpCode) {
+        self.receiver_is_this = false;
         let line = self.previous.line;
         self.current_chunk().write(opcode, line)
     }
@@ -768,6 +1807,7 @@ impl<'source> Parser<'source> {
     fn emit_return(&mut self) {
         match self.compiler.function_type {
             FunctionType::Initializer => self.emit(OpCode::GetLocal(0)),
+            FunctionType::Setter => self.emit(OpCode::GetLocal(1)),
             _ => self.emit(OpCode::Nil),
         }
         self.emit(OpCode::Return);
@@ -802,6 +1842,8 @@ impl<'source> Parser<'source> {
 
         match self.current_chunk().code[pos] {
             OpCode::JumpIfFalse(ref mut o) => *o = offset,
+            OpCode::JumpIfNil(ref mut o) => *o = offset,
+            OpCode::JumpIfNotNil(ref mut o) => *o = offset,
             OpCode::Jump(ref mut o) => *o = offset,
             _ => unreachable!(),
         }
@@ -838,6 +1880,17 @@ impl<'source> Parser<'source> {
             return;
         }
         self.panic_mode = true;
+        self.report(token, message);
+    }
+
+    /// Report an error that doesn't leave the parser lost, so there's no need to enter panic mode
+    fn error_without_sync(&mut self, message: &str) {
+        if !self.panic_mode {
+            self.report(self.previous, message);
+        }
+    }
+
+    fn report(&mut self, token: Token, message: &str) {
         eprint!("[line {}] Error", token.line);
 
         match token.token_type {
@@ -857,13 +1910,20 @@ impl<'source> Parser<'source> {
 enum Precedence {
     None,
     Assignment,
+    Conditional,
+    Coalesce,
     Or,
     And,
     Equality,
     Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Term,
     Factor,
     Unary,
+    Exponent,
     Call,
     Primary,
 }
@@ -872,14 +1932,21 @@ impl Precedence {
     fn next(&self) -> Precedence {
         match self {
             Precedence::None => Precedence::Assignment,
-            Precedence::Assignment => Precedence::Or,
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Coalesce,
+            Precedence::Coalesce => Precedence::Or,
             Precedence::Or => Precedence::And,
             Precedence::And => Precedence::Equality,
             Precedence::Equality => Precedence::Comparison,
-            Precedence::Comparison => Precedence::Term,
+            Precedence::Comparison => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
             Precedence::Term => Precedence::Factor,
             Precedence::Factor => Precedence::Unary,
-            Precedence::Unary => Precedence::Call,
+            Precedence::Unary => Precedence::Exponent,
+            Precedence::Exponent => Precedence::Call,
             Precedence::Call => Precedence::Primary,
             Precedence::Primary => Precedence::None,
         }
@@ -922,7 +1989,12 @@ impl<'source> ParseRuleTable<'source> {
                 array[index as usize] = MaybeUninit::new(rule);
             }
 
-            unsafe { std::mem::transmute::<_, [ParseRule; TokenType::COUNT]>(array) }
+            unsafe {
+                std::mem::transmute::<
+                    [MaybeUninit<ParseRule>; TokenType::COUNT],
+                    [ParseRule; TokenType::COUNT],
+                >(array)
+            }
         };
         Self(table)
     }
@@ -936,6 +2008,8 @@ impl<'source> ParseRuleTable<'source> {
             RightParen =>   ParseRule::new(None,                   None,                 P::None),
             LeftBrace =>    ParseRule::new(None,                   None,                 P::None),
             RightBrace =>   ParseRule::new(None,                   None,                 P::None),
+            LeftBracket =>  ParseRule::new(Some(Parser::list),     Some(Parser::subscript), P::Call),
+            RightBracket => ParseRule::new(None,                   None,                 P::None),
             Comma =>        ParseRule::new(None,                   None,                 P::None),
             Dot =>          ParseRule::new(None,                   Some(Parser::dot),    P::Call),
             Minus =>        ParseRule::new(Some(Parser::unary),    Some(Parser::binary), P::Term),
@@ -943,6 +2017,13 @@ impl<'source> ParseRuleTable<'source> {
             Semicolon =>    ParseRule::new(None,                   None,                 P::None),
             Slash =>        ParseRule::new(None,                   Some(Parser::binary), P::Factor),
             Star =>         ParseRule::new(None,                   Some(Parser::binary), P::Factor),
+            Percent =>      ParseRule::new(None,                   Some(Parser::binary), P::Factor),
+            Ampersand =>    ParseRule::new(None,                   Some(Parser::binary), P::BitAnd),
+            Pipe =>         ParseRule::new(None,                   Some(Parser::binary), P::BitOr),
+            Caret =>        ParseRule::new(None,                   Some(Parser::binary), P::BitXor),
+            Tilde =>        ParseRule::new(Some(Parser::unary),    None,                 P::None),
+            Colon =>        ParseRule::new(None,                   None,                 P::None),
+            Question =>     ParseRule::new(None,                   Some(Parser::conditional), P::Conditional),
             Bang =>         ParseRule::new(Some(Parser::unary),    None,                 P::None),
             BangEqual =>    ParseRule::new(None,                   Some(Parser::binary), P::Equality),
             Equal =>        ParseRule::new(None,                   None,                 P::None),
@@ -951,25 +2032,54 @@ impl<'source> ParseRuleTable<'source> {
             GreaterEqual => ParseRule::new(None,                   Some(Parser::binary), P::Comparison),
             Less =>         ParseRule::new(None,                   Some(Parser::binary), P::Comparison),
             LessEqual =>    ParseRule::new(None,                   Some(Parser::binary), P::Comparison),
+            LessLess =>     ParseRule::new(None,                   Some(Parser::binary), P::Shift),
+            GreaterGreater => ParseRule::new(None,                 Some(Parser::binary), P::Shift),
+            StarStar =>     ParseRule::new(None,                   Some(Parser::binary), P::Exponent),
+            TildeSlash =>   ParseRule::new(None,                   Some(Parser::binary), P::Factor),
+            QuestionQuestion => ParseRule::new(None,               Some(Parser::coalesce), P::Coalesce),
+            QuestionDot =>  ParseRule::new(None,                   Some(Parser::optional_chain), P::Call),
+            DotDotDot =>    ParseRule::new(None,                   None,                 P::None),
+            FatArrow =>     ParseRule::new(None,                   None,                 P::None),
+            PlusEqual =>    ParseRule::new(None,                   None,                 P::None),
+            MinusEqual =>   ParseRule::new(None,                   None,                 P::None),
+            StarEqual =>    ParseRule::new(None,                   None,                 P::None),
+            SlashEqual =>   ParseRule::new(None,                   None,                 P::None),
+            TildeSlashEqual => ParseRule::new(None,                None,                 P::None),
+            PercentEqual => ParseRule::new(None,                   None,                 P::None),
+            StarStarEqual => ParseRule::new(None,                  None,                 P::None),
+            AmpersandEqual => ParseRule::new(None,                 None,                 P::None),
+            PipeEqual =>    ParseRule::new(None,                   None,                 P::None),
+            CaretEqual =>   ParseRule::new(None,                   None,                 P::None),
+            LessLessEqual => ParseRule::new(None,                  None,                 P::None),
+            GreaterGreaterEqual => ParseRule::new(None,            None,                 P::None),
             Identifier =>   ParseRule::new(Some(Parser::variable), None,                 P::None),
+            PrivateIdentifier => ParseRule::new(None,              None,                 P::None),
             String =>       ParseRule::new(Some(Parser::string),   None,                 P::None),
             Number =>       ParseRule::new(Some(Parser::number),   None,                 P::None),
+            Integer =>      ParseRule::new(Some(Parser::integer),  None,                 P::None),
             And =>          ParseRule::new(None,                   Some(Parser::and),    P::And),
+            Case =>         ParseRule::new(None,                   None,                 P::None),
             Class =>        ParseRule::new(None,                   None,                 P::None),
+            Const =>        ParseRule::new(None,                   None,                 P::None),
             Else =>         ParseRule::new(None,                   None,                 P::None),
+            Enum =>         ParseRule::new(None,                   None,                 P::None),
             False =>        ParseRule::new(Some(Parser::literal),  None,                 P::None),
             For =>          ParseRule::new(None,                   None,                 P::None),
             Fun =>          ParseRule::new(None,                   None,                 P::None),
             If =>           ParseRule::new(None,                   None,                 P::None),
+            Is =>           ParseRule::new(None,                   Some(Parser::binary), P::Comparison),
+            Match =>        ParseRule::new(None,                   None,                 P::None),
             Nil =>          ParseRule::new(Some(Parser::literal),  None,                 P::None),
             Or =>           ParseRule::new(None,                   Some(Parser::or),     P::Or),
             Print =>        ParseRule::new(None,                   None,                 P::None),
             Return =>       ParseRule::new(None,                   None,                 P::None),
             Super =>        ParseRule::new(Some(Parser::super_),   None,                 P::None),
             This =>         ParseRule::new(Some(Parser::this),     None,                 P::None),
+            Trait =>        ParseRule::new(None,                   None,                 P::None),
             True =>         ParseRule::new(Some(Parser::literal),  None,                 P::None),
             Var =>          ParseRule::new(None,                   None,                 P::None),
             While =>        ParseRule::new(None,                   None,                 P::None),
+            With =>         ParseRule::new(None,                   None,                 P::None),
             Error =>        ParseRule::new(None,                   None,                 P::None),
             Eof =>          ParseRule::new(None,                   None,                 P::None),
         }
@@ -0,0 +1,180 @@
+use std::{
+    env,
+    fmt::{self, Display, Formatter},
+    path::{Component, Path, PathBuf},
+};
+
+/// What a script may touch outside the VM, chosen by the embedder when it creates the `Vm`
+pub struct Permissions {
+    pub read: Access,
+    pub write: Access,
+    /// Reading and setting environment variables
+    pub env: bool,
+    /// Running other processes
+    pub run: bool,
+    /// Opening network connections and listening for them
+    pub net: bool,
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Self {
+            read: Access::Allowed,
+            write: Access::Allowed,
+            env: true,
+            run: true,
+            net: true,
+        }
+    }
+
+    /// For sandboxes, where scripts can only compute
+    pub fn none() -> Self {
+        Self {
+            read: Access::Denied,
+            write: Access::Denied,
+            env: false,
+            run: false,
+            net: false,
+        }
+    }
+
+    /// Whether the operation is allowed, on the given path for reads and writes
+    pub fn allows(&self, permission: Permission, path: Option<&str>) -> bool {
+        match permission {
+            Permission::Read => self.read.allows(path),
+            Permission::Write => self.write.allows(path),
+            Permission::Env => self.env,
+            Permission::Run => self.run,
+            Permission::Net => self.net,
+        }
+    }
+}
+
+impl Default for Permissions {
+    /// Files and the environment are open, but running processes and the network need asking for
+    fn default() -> Self {
+        Self {
+            read: Access::Allowed,
+            write: Access::Allowed,
+            env: true,
+            run: false,
+            net: false,
+        }
+    }
+}
+
+/// Access to the file system
+pub enum Access {
+    Denied,
+    Allowed,
+    /// Only these files and directories, along with everything in the directories
+    Paths(Vec<PathBuf>),
+}
+
+impl Access {
+    fn allows(&self, path: Option<&str>) -> bool {
+        match (self, path) {
+            (Access::Denied, _) => false,
+            (Access::Allowed, _) => true,
+            (Access::Paths(_), None) => false,
+            (Access::Paths(allowed), Some(path)) => {
+                let path = resolve(Path::new(path));
+                allowed
+                    .iter()
+                    .any(|allowed| path.starts_with(resolve(allowed)))
+            }
+        }
+    }
+
+    /// Limits access to the paths, adding to those already in an allow-list
+    pub fn allow_paths(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        match self {
+            Access::Paths(allowed) => allowed.extend(paths),
+            _ => *self = Access::Paths(paths.into_iter().collect()),
+        }
+    }
+}
+
+/// The absolute path with symlinks followed, so "data/../secret" or a link out of an allowed
+/// directory can't get around an allow-list. The part of the path that doesn't exist yet, like a
+/// file about to be written, is taken as it is, without its "." and "..".
+fn resolve(path: &Path) -> PathBuf {
+    let path = match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    };
+    let mut existing = path.as_path();
+    let mut missing = vec![];
+    let mut resolved = loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            break resolved;
+        }
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(component)) => {
+                missing.push(component);
+                existing = parent;
+            }
+            _ => break PathBuf::new(),
+        }
+    };
+    for component in missing.into_iter().rev() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+/// A kind of operation that needs permission
+#[derive(Clone, Copy)]
+pub enum Permission {
+    Read,
+    Write,
+    Env,
+    Run,
+    Net,
+}
+
+impl Display for Permission {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Env => "env",
+            Permission::Run => "run",
+            Permission::Net => "net",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_allow_list() {
+        let access = Access::Paths(vec![PathBuf::from("tests/json")]);
+        assert!(access.allows(Some("tests/json/config.json")));
+        assert!(access.allows(Some("./tests/json/new/file.json")));
+        assert!(access.allows(Some("tests/json")));
+        assert!(!access.allows(Some("tests/json/../enum.lox")));
+        assert!(!access.allows(Some("tests/jsonx")));
+        assert!(!access.allows(Some("/etc/passwd")));
+        assert!(!access.allows(None));
+    }
+
+    #[test]
+    fn categories() {
+        let permissions = Permissions::default();
+        assert!(permissions.allows(Permission::Read, Some("anything")));
+        assert!(permissions.allows(Permission::Env, None));
+        assert!(!permissions.allows(Permission::Run, None));
+        assert!(!Permissions::none().allows(Permission::Write, Some("file")));
+        assert!(Permissions::all().allows(Permission::Net, None));
+    }
+}
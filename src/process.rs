@@ -0,0 +1,317 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+};
+
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{Class, Instance},
+    permissions::Permission,
+    value::Value,
+    vm::Vm,
+};
+
+/// The `process` module, whose `run` needs the run permission
+pub fn define_natives(vm: &mut Vm) {
+    let mut process = vm.define_class("process");
+    let (name, run) = vm.new_variadic_native("run", 1, run);
+    process.statics.insert(name, run);
+
+    let result_name = vm.intern(RESULT_CLASS.to_string());
+    vm.push_root(Value::String(result_name));
+    let result_class = vm.alloc(Class::new(result_name));
+    vm.pop_root();
+    process
+        .statics
+        .insert(result_name, Value::Class(result_class));
+}
+
+/// The class of the instances returned by `process.run()`
+const RESULT_CLASS: &str = "Result";
+
+/// What to do with the output written to stdout or stderr
+enum Output {
+    /// Return it as a string in the result
+    Capture,
+    /// Pass it straight through to this process's own stream
+    Inherit,
+    /// Call a function with each line as it arrives, without its line ending
+    Lines(Value),
+}
+
+/// `process.run(cmd, args, options)` runs a command and waits for it to finish, returning a
+/// `process.Result` with its exit `status`, which is nil if it was killed by a signal, and its
+/// captured `stdout` and `stderr`.
+///
+/// The options are the fields of any instance, such as a `json.Object()`:
+/// - `cwd`, the directory to run in
+/// - `env`, an instance whose fields are set as environment variables, or removed if nil
+/// - `stdin`, a string written to the command's input
+/// - `stdout` and `stderr`, "capture" by default, "inherit" to pass the output through, or a
+///   function called with each line as it's written, in which case the result's field is nil
+fn run(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(3, args)?;
+    vm.check_permission(Permission::Run, None)?;
+    let program = match args[0] {
+        Value::String(program) => program,
+        _ => return vm.runtime_error("Command must be a string."),
+    };
+    let mut command = Command::new(program.as_str());
+    match args.get(1) {
+        None | Some(Value::Nil) => {}
+        Some(Value::List(list)) => {
+            for arg in &list.items {
+                match arg {
+                    Value::String(arg) => command.arg(arg.as_str()),
+                    _ => return vm.runtime_error("Command arguments must be strings."),
+                };
+            }
+        }
+        Some(_) => return vm.runtime_error("Command arguments must be a list."),
+    }
+    let options = match args.get(2) {
+        None | Some(Value::Nil) => None,
+        Some(Value::Instance(options)) => Some(*options),
+        Some(_) => return vm.runtime_error("Process options must be an instance."),
+    };
+
+    let mut stdin = None;
+    let mut stdout = Output::Capture;
+    let mut stderr = Output::Capture;
+    if let Some(options) = options {
+        if let Some(cwd) = option(vm, options, "cwd") {
+            match cwd {
+                Value::String(cwd) => command.current_dir(cwd.as_str()),
+                _ => return vm.runtime_error("Option 'cwd' must be a string."),
+            };
+        }
+        if let Some(env) = option(vm, options, "env") {
+            let env = match env {
+                Value::Instance(env) => env,
+                _ => return vm.runtime_error("Option 'env' must be an instance."),
+            };
+            for (name, value) in env.fields.iter() {
+                match value {
+                    Value::Nil => command.env_remove(name.as_str()),
+                    Value::String(value) => command.env(name.as_str(), value.as_str()),
+                    _ => return vm.runtime_error("Environment variables must be strings."),
+                };
+            }
+        }
+        if let Some(input) = option(vm, options, "stdin") {
+            match input {
+                Value::String(input) => stdin = Some(input.as_str().to_string()),
+                _ => return vm.runtime_error("Option 'stdin' must be a string."),
+            }
+        }
+        if let Some(value) = option(vm, options, "stdout") {
+            stdout = output_option(vm, "stdout", value)?;
+        }
+        if let Some(value) = option(vm, options, "stderr") {
+            stderr = output_option(vm, "stderr", value)?;
+        }
+    }
+
+    command
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(stdio(&stdout))
+        .stderr(stdio(&stderr));
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            return vm.runtime_error(&format!("Could not run '{}': {}.", program.as_str(), err))
+        }
+    };
+
+    // Each pipe gets its own thread, so a command filling one pipe while its other pipe is
+    // unread can't block forever. Lines come back here, where the VM can call functions.
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // A command that exits without reading its input is fine
+        thread::spawn(move || pipe.write_all(input.as_bytes()));
+    }
+    let (sender, receiver) = mpsc::channel();
+    let mut readers = vec![];
+    if let Some(pipe) = child.stdout.take() {
+        readers.push(read_pipe(pipe, Stream::Stdout, &stdout, sender.clone()));
+    }
+    if let Some(pipe) = child.stderr.take() {
+        readers.push(read_pipe(pipe, Stream::Stderr, &stderr, sender.clone()));
+    }
+    drop(sender);
+
+    let mut captured_stdout = Vec::new();
+    let mut captured_stderr = Vec::new();
+    loop {
+        let (stream, bytes) = match receiver.recv_timeout(Vm::POLL_INTERVAL) {
+            Ok(received) => received,
+            Err(RecvTimeoutError::Timeout) => {
+                if let Err(err) = vm.check_interrupt() {
+                    kill(&mut child);
+                    return Err(err);
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let (output, captured) = match stream {
+            Stream::Stdout => (&stdout, &mut captured_stdout),
+            Stream::Stderr => (&stderr, &mut captured_stderr),
+        };
+        match output {
+            Output::Lines(function) => {
+                let line = String::from_utf8_lossy(trim_line_ending(&bytes)).into_owned();
+                let line = Value::String(vm.intern(line));
+                if let Err(err) = vm.call_function(*function, &[line]) {
+                    kill(&mut child);
+                    return Err(err);
+                }
+            }
+            _ => captured.extend(bytes),
+        }
+    }
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {
+                if let Err(err) = vm.check_interrupt() {
+                    kill(&mut child);
+                    return Err(err);
+                }
+                thread::sleep(Vm::POLL_INTERVAL);
+            }
+            Err(err) => {
+                return vm.runtime_error(&format!("Could not run '{}': {}.", program.as_str(), err))
+            }
+        }
+    };
+
+    let result_class = {
+        let name = vm.intern(RESULT_CLASS.to_string());
+        match this {
+            Value::Class(process) => match process.statics.get(name) {
+                Some(Value::Class(class)) => class,
+                _ => return vm.runtime_error("process.Result must be a class."),
+            },
+            _ => unreachable!(),
+        }
+    };
+    let result = vm.alloc(Instance::new(result_class));
+    // Keep the result reachable while its fields are allocated
+    vm.push_root(Value::Instance(result));
+    let status = match status.code() {
+        Some(code) => Value::Int(code as i64),
+        None => Value::Nil,
+    };
+    set_field(vm, result, "status", status);
+    for (name, output, captured) in [
+        ("stdout", &stdout, captured_stdout),
+        ("stderr", &stderr, captured_stderr),
+    ] {
+        let value = match output {
+            Output::Capture => {
+                Value::String(vm.intern(String::from_utf8_lossy(&captured).into_owned()))
+            }
+            _ => Value::Nil,
+        };
+        vm.push_root(value);
+        set_field(vm, result, name, value);
+        vm.pop_root();
+    }
+    vm.pop_root();
+    Ok(Value::Instance(result))
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Sends what's read from a pipe, a line at a time when it's going to a function and in chunks
+/// otherwise
+fn read_pipe(
+    pipe: impl Read + Send + 'static,
+    stream: Stream,
+    output: &Output,
+    sender: mpsc::Sender<(Stream, Vec<u8>)>,
+) -> thread::JoinHandle<()> {
+    let by_line = matches!(output, Output::Lines(_));
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut bytes = vec![];
+            let read = if by_line {
+                reader.read_until(b'\n', &mut bytes)
+            } else {
+                let mut chunk = [0; 8192];
+                reader
+                    .read(&mut chunk)
+                    .inspect(|read| bytes.extend(&chunk[..*read]))
+            };
+            match read {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if sender.send((stream, bytes)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Stops a command the script has stopped waiting for. Its readers finish once its pipes close.
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn stdio(output: &Output) -> Stdio {
+    match output {
+        Output::Inherit => Stdio::inherit(),
+        _ => Stdio::piped(),
+    }
+}
+
+fn output_option(vm: &Vm, name: &str, value: Value) -> Result<Output> {
+    match value {
+        Value::String(mode) if mode.as_str() == "capture" => Ok(Output::Capture),
+        Value::String(mode) if mode.as_str() == "inherit" => Ok(Output::Inherit),
+        Value::Closure(_) | Value::NativeFunction(_) | Value::BoundMethod(_) => {
+            Ok(Output::Lines(value))
+        }
+        _ => vm.runtime_error(&format!(
+            "Option '{}' must be \"capture\", \"inherit\" or a function.",
+            name
+        )),
+    }
+}
+
+/// A field of the options, where nil counts as missing
+fn option(vm: &mut Vm, options: GcRef<Instance>, name: &str) -> Option<Value> {
+    let name = vm.intern(name.to_string());
+    options
+        .fields
+        .get(name)
+        .filter(|value| !matches!(value, Value::Nil))
+}
+
+fn set_field(vm: &mut Vm, mut instance: GcRef<Instance>, name: &str, value: Value) {
+    let name = vm.intern(name.to_string());
+    instance.fields.insert(name, value);
+}
@@ -0,0 +1,98 @@
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{Instance, List, LoxString},
+    table::Table,
+    value::Value,
+    vm::Vm,
+};
+
+/// Natives for inspecting objects at runtime
+pub fn define_natives(vm: &mut Vm) {
+    vm.define_native("classOf", 1, class_of);
+    vm.define_native("fields", 1, fields);
+    vm.define_native("methods", 1, methods);
+    vm.define_native("hasField", 2, has_field);
+    vm.define_native("getField", 2, get_field);
+    vm.define_native("setField", 3, set_field);
+}
+
+fn class_of(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    Ok(vm.class_of(args[0]).map_or(Value::Nil, Value::Class))
+}
+
+fn fields(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    match args[0] {
+        Value::Instance(instance) => Ok(names(vm, &instance.fields)),
+        _ => vm.runtime_error("Argument must be an instance."),
+    }
+}
+
+fn methods(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    match args[0] {
+        Value::Class(class) => Ok(names(vm, &class.methods)),
+        _ => vm.runtime_error("Argument must be a class."),
+    }
+}
+
+fn has_field(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let name = field_name(vm, args[1])?;
+    Ok(Value::Bool(match args[0] {
+        Value::Instance(instance) => instance.fields.get(name).is_some(),
+        _ => false,
+    }))
+}
+
+fn get_field(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let instance = instance(vm, args[0])?;
+    let name = public_field_name(vm, args[1])?;
+    match instance.fields.get(name) {
+        Some(value) => Ok(value),
+        None => vm.runtime_error(&format!("Undefined property '{}'.", name.as_str())),
+    }
+}
+
+fn set_field(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    let mut instance = instance(vm, args[0])?;
+    let name = public_field_name(vm, args[1])?;
+    if instance.class.is_enum {
+        return vm.runtime_error("Cannot modify an enum variant.");
+    }
+    instance.fields.insert(name, args[2]);
+    Ok(args[2])
+}
+
+/// A list of the keys in a table, sorted so the output doesn't depend on hashing
+fn names(vm: &mut Vm, table: &Table) -> Value {
+    let mut names: Vec<_> = table.iter().map(|(name, _)| name).collect();
+    names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    let names = names.into_iter().map(Value::String).collect();
+    // The names are all keys of a table reachable from the arguments, so they survive this allocation
+    Value::List(vm.alloc(List::new(names)))
+}
+
+fn instance(vm: &Vm, value: Value) -> Result<GcRef<Instance>> {
+    match value {
+        Value::Instance(instance) => Ok(instance),
+        _ => vm.runtime_error("Only instances have fields."),
+    }
+}
+
+fn field_name(vm: &Vm, value: Value) -> Result<GcRef<LoxString>> {
+    match value {
+        Value::String(name) => Ok(name),
+        _ => vm.runtime_error("Field name must be a string."),
+    }
+}
+
+/// Private members can only be reached through 'this', never by name
+fn public_field_name(vm: &Vm, value: Value) -> Result<GcRef<LoxString>> {
+    let name = field_name(vm, value)?;
+    if name.as_str().starts_with('#') {
+        return vm.runtime_error(&format!(
+            "Cannot access private member '{}' by name.",
+            name.as_str()
+        ));
+    }
+    Ok(name)
+}
@@ -0,0 +1,157 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use ::regex::Regex;
+
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{List, LoxString},
+    value::Value,
+    vm::Vm,
+};
+
+/// The `Regex` class. `Regex.compile(pattern)` returns a pattern whose methods search strings,
+/// with the syntax of Rust's regex crate.
+pub fn define_natives(vm: &mut Vm) {
+    let class = vm.define_class("Regex");
+    vm.define_static_native(class, "compile", 1, compile);
+
+    vm.define_method_native(class, "test", 1, test);
+    vm.define_method_native(class, "find", 1, find);
+    vm.define_method_native(class, "findAll", 1, find_all);
+    vm.define_method_native(class, "captures", 1, captures);
+    vm.define_method_native(class, "replace", 2, replace);
+}
+
+/// Compiled patterns are kept once this many are cached, and the cache starts over
+const MAX_CACHED: usize = 256;
+
+thread_local! {
+    /// Compiling is much slower than matching, so `Regex.compile` in a loop reuses each pattern
+    static CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+fn compile(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let pattern = string_arg(vm, args[0], "Pattern")?;
+    let cached = CACHE.with(|cache| cache.borrow().get(pattern.as_str()).cloned());
+    let regex = match cached {
+        Some(regex) => regex,
+        None => match Regex::new(pattern.as_str()) {
+            Ok(regex) => {
+                CACHE.with(|cache| {
+                    let mut cache = cache.borrow_mut();
+                    if cache.len() >= MAX_CACHED {
+                        cache.clear();
+                    }
+                    cache.insert(pattern.as_str().to_string(), regex.clone());
+                });
+                regex
+            }
+            Err(err) => return invalid_pattern(vm, pattern, err),
+        },
+    };
+    let class = match this {
+        Value::Class(class) => class,
+        _ => unreachable!(),
+    };
+    Ok(vm.new_host(class, Box::new(regex)))
+}
+
+/// Whether the pattern matches anywhere in the string
+fn test(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let regex = regex(vm, this)?;
+    let text = string_arg(vm, args[0], "Text")?;
+    Ok(Value::Bool(regex.is_match(text.as_str())))
+}
+
+/// The first match, or nil if there isn't one
+fn find(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let regex = regex(vm, this)?;
+    let text = string_arg(vm, args[0], "Text")?;
+    match regex.find(text.as_str()) {
+        Some(found) => Ok(Value::String(vm.intern(found.as_str().to_string()))),
+        None => Ok(Value::Nil),
+    }
+}
+
+/// Every match that doesn't overlap an earlier one
+fn find_all(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let regex = regex(vm, this)?;
+    let text = string_arg(vm, args[0], "Text")?;
+    let found = regex
+        .find_iter(text.as_str())
+        .map(|found| found.as_str().to_string())
+        .collect();
+    Ok(vm.new_string_list(found))
+}
+
+/// The groups of the first match, starting with the whole match, or nil if there isn't one.
+/// Groups that didn't take part in the match are nil.
+fn captures(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let regex = regex(vm, this)?;
+    let text = string_arg(vm, args[0], "Text")?;
+    let groups: Vec<Option<String>> = match regex.captures(text.as_str()) {
+        Some(captures) => captures
+            .iter()
+            .map(|group| group.map(|group| group.as_str().to_string()))
+            .collect(),
+        None => return Ok(Value::Nil),
+    };
+
+    let mut list = vm.alloc(List::new(Vec::with_capacity(groups.len())));
+    // Keep the list reachable while its strings are allocated
+    vm.push_root(Value::List(list));
+    for group in groups {
+        let item = match group {
+            Some(group) => Value::String(vm.intern(group)),
+            None => Value::Nil,
+        };
+        list.items.push(item);
+    }
+    vm.pop_root();
+    Ok(Value::List(list))
+}
+
+/// Replaces every match, where the replacement can refer to groups as `$1` or `${name}`
+fn replace(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let regex = regex(vm, this)?;
+    let text = string_arg(vm, args[0], "Text")?;
+    let replacement = string_arg(vm, args[1], "Replacement")?;
+    let replaced = regex
+        .replace_all(text.as_str(), replacement.as_str())
+        .into_owned();
+    Ok(Value::String(vm.intern(replaced)))
+}
+
+/// The compiled pattern, which is cheap to clone
+fn regex(vm: &Vm, this: Value) -> Result<Regex> {
+    match this {
+        Value::Host(host) => match host.data.downcast_ref::<Regex>() {
+            Some(regex) => Ok(regex.clone()),
+            None => vm.runtime_error("Expected a regex."),
+        },
+        _ => vm.runtime_error("Expected a regex."),
+    }
+}
+
+fn string_arg(vm: &Vm, value: Value, what: &str) -> Result<GcRef<LoxString>> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => vm.runtime_error(&format!("{} must be a string.", what)),
+    }
+}
+
+fn invalid_pattern<T>(vm: &Vm, pattern: GcRef<LoxString>, err: ::regex::Error) -> Result<T> {
+    // Syntax errors come with a drawing of where they are, but only the last line says what's wrong
+    let message = err.to_string();
+    let reason = message
+        .lines()
+        .last()
+        .unwrap_or_default()
+        .trim_start_matches("error: ");
+    vm.runtime_error(&format!(
+        "Invalid regex '{}': {}.",
+        pattern.as_str(),
+        reason
+    ))
+}
@@ -1,6 +1,7 @@
 use num_enum::IntoPrimitive;
 use strum::{EnumCount, EnumIter};
 
+#[derive(Clone)]
 pub struct Scanner<'source> {
     source: &'source str,
     start: usize,
@@ -31,21 +32,77 @@ impl<'source> Scanner<'source> {
             b')' => self.make_token(TokenType::RightParen),
             b'{' => self.make_token(TokenType::LeftBrace),
             b'}' => self.make_token(TokenType::RightBrace),
+            b'[' => self.make_token(TokenType::LeftBracket),
+            b']' => self.make_token(TokenType::RightBracket),
             b';' => self.make_token(TokenType::Semicolon),
             b',' => self.make_token(TokenType::Comma),
+            b'.' if self.peek() == b'.' && self.peek_next() == b'.' => {
+                self.advance();
+                self.advance();
+                self.make_token(TokenType::DotDotDot)
+            }
             b'.' => self.make_token(TokenType::Dot),
+            b':' => self.make_token(TokenType::Colon),
+            b'?' if self.match_advance(b'?') => self.make_token(TokenType::QuestionQuestion),
+            b'?' if self.match_advance(b'.') => self.make_token(TokenType::QuestionDot),
+            b'?' => self.make_token(TokenType::Question),
+            b'-' if self.match_advance(b'=') => self.make_token(TokenType::MinusEqual),
             b'-' => self.make_token(TokenType::Minus),
+            b'+' if self.match_advance(b'=') => self.make_token(TokenType::PlusEqual),
             b'+' => self.make_token(TokenType::Plus),
+            b'/' if self.match_advance(b'=') => self.make_token(TokenType::SlashEqual),
             b'/' => self.make_token(TokenType::Slash),
+            b'*' if self.match_advance(b'*') => {
+                if self.match_advance(b'=') {
+                    self.make_token(TokenType::StarStarEqual)
+                } else {
+                    self.make_token(TokenType::StarStar)
+                }
+            }
+            b'*' if self.match_advance(b'=') => self.make_token(TokenType::StarEqual),
             b'*' => self.make_token(TokenType::Star),
+            b'%' if self.match_advance(b'=') => self.make_token(TokenType::PercentEqual),
+            b'%' => self.make_token(TokenType::Percent),
+            b'&' if self.match_advance(b'=') => self.make_token(TokenType::AmpersandEqual),
+            b'&' => self.make_token(TokenType::Ampersand),
+            b'|' if self.match_advance(b'=') => self.make_token(TokenType::PipeEqual),
+            b'|' => self.make_token(TokenType::Pipe),
+            b'^' if self.match_advance(b'=') => self.make_token(TokenType::CaretEqual),
+            b'^' => self.make_token(TokenType::Caret),
+            b'~' if self.match_advance(b'/') => {
+                if self.match_advance(b'=') {
+                    self.make_token(TokenType::TildeSlashEqual)
+                } else {
+                    self.make_token(TokenType::TildeSlash)
+                }
+            }
+            b'~' => self.make_token(TokenType::Tilde),
             b'!' if self.match_advance(b'=') => self.make_token(TokenType::BangEqual),
             b'!' => self.make_token(TokenType::Bang),
             b'=' if self.match_advance(b'=') => self.make_token(TokenType::EqualEqual),
+            b'=' if self.match_advance(b'>') => self.make_token(TokenType::FatArrow),
             b'=' => self.make_token(TokenType::Equal),
             b'<' if self.match_advance(b'=') => self.make_token(TokenType::LessEqual),
+            b'<' if self.match_advance(b'<') => {
+                if self.match_advance(b'=') {
+                    self.make_token(TokenType::LessLessEqual)
+                } else {
+                    self.make_token(TokenType::LessLess)
+                }
+            }
             b'<' => self.make_token(TokenType::Less),
             b'>' if self.match_advance(b'=') => self.make_token(TokenType::GreaterEqual),
+            b'>' if self.match_advance(b'>') => {
+                if self.match_advance(b'=') {
+                    self.make_token(TokenType::GreaterGreaterEqual)
+                } else {
+                    self.make_token(TokenType::GreaterGreater)
+                }
+            }
             b'>' => self.make_token(TokenType::Greater),
+            b'#' if self.peek().is_ascii_alphabetic() || self.peek() == b'_' => {
+                self.private_identifier()
+            }
             b'"' => self.string(),
             c if c.is_ascii_digit() => self.number(),
             c if c.is_ascii_alphabetic() || c == b'_' => self.identifier(),
@@ -83,15 +140,10 @@ impl<'source> Scanner<'source> {
                     self.advance();
                 }
                 // Comments
-                b'/' => {
-                    if self.peek_next() == b'/' {
-                        // A comment goes until the end of the line
-                        while !self.is_at_end() && self.peek() != b'\n' {
-                            self.advance();
-                        }
-                    } else {
-                        // This slash is actually a token
-                        return;
+                b'/' if self.peek_next() == b'/' => {
+                    // A comment goes until the end of the line
+                    while !self.is_at_end() && self.peek() != b'\n' {
+                        self.advance();
                     }
                 }
                 _ => {
@@ -129,12 +181,14 @@ impl<'source> Scanner<'source> {
             self.advance();
 
             // Consume the rest of the numbers
-            while self.peek().is_ascii_digit() {
+            while !self.is_at_end() && self.peek().is_ascii_digit() {
                 self.advance();
             }
-        }
 
-        self.make_token(TokenType::Number)
+            self.make_token(TokenType::Number)
+        } else {
+            self.make_token(TokenType::Integer)
+        }
     }
 
     fn identifier(&mut self) -> Token<'source> {
@@ -144,19 +198,45 @@ impl<'source> Scanner<'source> {
         self.make_token(self.identifier_type())
     }
 
+    /// A `#name` member, only reachable through 'this' inside its class
+    fn private_identifier(&mut self) -> Token<'source> {
+        while self.peek().is_ascii_alphanumeric() || self.peek() == b'_' {
+            self.advance();
+        }
+        self.make_token(TokenType::PrivateIdentifier)
+    }
+
     fn identifier_type(&self) -> TokenType {
         match self.source.as_bytes()[self.start] {
             b'a' => self.check_keyword(1, "nd", TokenType::And),
-            b'c' => self.check_keyword(1, "lass", TokenType::Class),
-            b'e' => self.check_keyword(1, "lse", TokenType::Else),
-            b'i' => self.check_keyword(1, "f", TokenType::If),
+            b'c' if self.current - self.start > 1 => match self.source.as_bytes()[self.start + 1] {
+                b'a' => self.check_keyword(2, "se", TokenType::Case),
+                b'l' => self.check_keyword(2, "ass", TokenType::Class),
+                b'o' => self.check_keyword(2, "nst", TokenType::Const),
+                _ => TokenType::Identifier,
+            },
+            b'e' if self.current - self.start > 1 => match self.source.as_bytes()[self.start + 1] {
+                b'l' => self.check_keyword(2, "se", TokenType::Else),
+                b'n' => self.check_keyword(2, "um", TokenType::Enum),
+                _ => TokenType::Identifier,
+            },
+            b'i' if self.current - self.start > 1 => match self.source.as_bytes()[self.start + 1] {
+                b'f' => self.check_keyword(2, "", TokenType::If),
+                b's' => self.check_keyword(2, "", TokenType::Is),
+                _ => TokenType::Identifier,
+            },
+            b'm' => self.check_keyword(1, "atch", TokenType::Match),
             b'n' => self.check_keyword(1, "il", TokenType::Nil),
             b'o' => self.check_keyword(1, "r", TokenType::Or),
             b'p' => self.check_keyword(1, "rint", TokenType::Print),
             b'r' => self.check_keyword(1, "eturn", TokenType::Return),
             b's' => self.check_keyword(1, "uper", TokenType::Super),
             b'v' => self.check_keyword(1, "ar", TokenType::Var),
-            b'w' => self.check_keyword(1, "hile", TokenType::While),
+            b'w' if self.current - self.start > 1 => match self.source.as_bytes()[self.start + 1] {
+                b'h' => self.check_keyword(2, "ile", TokenType::While),
+                b'i' => self.check_keyword(2, "th", TokenType::With),
+                _ => TokenType::Identifier,
+            },
             b'f' if self.current - self.start > 1 => match self.source.as_bytes()[self.start + 1] {
                 b'a' => self.check_keyword(2, "lse", TokenType::False),
                 b'o' => self.check_keyword(2, "r", TokenType::For),
@@ -165,7 +245,13 @@ impl<'source> Scanner<'source> {
             },
             b't' if self.current - self.start > 1 => match self.source.as_bytes()[self.start + 1] {
                 b'h' => self.check_keyword(2, "is", TokenType::This),
-                b'r' => self.check_keyword(2, "ue", TokenType::True),
+                b'r' if self.current - self.start > 2 => {
+                    match self.source.as_bytes()[self.start + 2] {
+                        b'a' => self.check_keyword(3, "it", TokenType::Trait),
+                        b'u' => self.check_keyword(3, "e", TokenType::True),
+                        _ => TokenType::Identifier,
+                    }
+                }
                 _ => TokenType::Identifier,
             },
             _ => TokenType::Identifier,
@@ -186,7 +272,11 @@ impl<'source> Scanner<'source> {
     }
 
     fn peek(&self) -> u8 {
-        self.source.as_bytes()[self.current]
+        if self.is_at_end() {
+            b'\0'
+        } else {
+            self.source.as_bytes()[self.current]
+        }
     }
 
     fn peek_next(&self) -> u8 {
@@ -244,6 +334,15 @@ impl<'source> Token<'source> {
         }
     }
 
+    /// The hidden local holding the class whose body is being compiled
+    pub const fn class() -> Token<'source> {
+        Token {
+            token_type: TokenType::Class,
+            lexeme: "class",
+            line: 0,
+        }
+    }
+
     pub const fn super_() -> Token<'source> {
         Token {
             token_type: TokenType::Super,
@@ -260,6 +359,8 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -267,6 +368,13 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    Colon,
+    Question,
 
     // One or two character tokens.
     Bang,
@@ -277,29 +385,60 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    StarStar,
+    TildeSlash,
+    QuestionQuestion,
+    QuestionDot,
+    FatArrow,
+    DotDotDot,
+
+    // Compound assignment.
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    TildeSlashEqual,
+    PercentEqual,
+    StarStarEqual,
+    AmpersandEqual,
+    PipeEqual,
+    CaretEqual,
+    LessLessEqual,
+    GreaterGreaterEqual,
 
     // Literals.
     Identifier,
+    PrivateIdentifier,
     String,
     Number,
+    Integer,
 
     // Keywords.
     And,
+    Case,
     Class,
+    Const,
     Else,
+    Enum,
     False,
     For,
     Fun,
     If,
+    Is,
+    Match,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Trait,
     True,
     Var,
     While,
+    With,
 
     Error,
     Eof,
@@ -40,11 +40,11 @@ where
         }
     }
 
-    pub fn pop_n(&mut self, num: usize) -> &[T] {
+    /// The top `num` elements, from bottom to top
+    pub fn peek_n(&self, num: usize) -> &[T] {
         debug_assert!(self.index >= num);
         unsafe {
-            self.index -= num;
-            std::slice::from_raw_parts(self.data.get_unchecked_mut(self.index).as_ptr(), num)
+            std::slice::from_raw_parts(self.data.get_unchecked(self.index - num).as_ptr(), num)
         }
     }
 
@@ -59,7 +59,7 @@ where
 
     pub fn peek(&self, distance: usize) -> &T {
         debug_assert!(distance < self.index);
-        let index = (self.index - distance - 1) as usize;
+        let index = self.index - distance - 1;
         unsafe { self.data.get_unchecked(index).assume_init_ref() }
     }
 
@@ -129,7 +129,7 @@ mod tests {
             stack.push(i);
             assert_eq!(stack.peek(0), &i);
             for j in 0..i {
-                assert_eq!(stack.read(j as usize), &j);
+                assert_eq!(stack.read(j), &j);
             }
         }
 
@@ -82,6 +82,13 @@ impl Table {
         true
     }
 
+    /// Every key and value in the table, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (GcRef<LoxString>, Value)> + '_ {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.key.map(|key| (key, entry.value)))
+    }
+
     pub fn find_string(&self, string: &str, hash: u32) -> Option<GcRef<LoxString>> {
         if self.count == 0 {
             return None;
@@ -296,6 +303,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn iteration() {
+        // Generate some strings
+        let mut strings: Vec<_> = (0..100).map(|n| LoxString::new(n.to_string())).collect();
+
+        // Simulate being held by gc
+        let refs = make_refs(&mut strings);
+
+        // Insert into Table, then remove some to leave tombstones behind
+        let mut t = Table::new();
+        for key in &refs {
+            let num = str_to_num(*key) as f64;
+            t.insert(*key, Value::Number(num));
+        }
+        for key in refs.iter().step_by(2) {
+            t.remove(*key);
+        }
+
+        // Check only the remaining entries are visited
+        let mut nums: Vec<_> = t
+            .iter()
+            .map(|(key, value)| (str_to_num(key), to_num(value)))
+            .collect();
+        nums.sort();
+        let expected: Vec<_> = (0..100).skip(1).step_by(2).map(|n| (n, n)).collect();
+        assert_eq!(nums, expected);
+    }
+
     fn make_refs(strings: &mut [LoxString]) -> Vec<GcRef<LoxString>> {
         strings
             .iter_mut()
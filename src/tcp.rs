@@ -0,0 +1,354 @@
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+};
+
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{Class, Host, LoxString},
+    permissions::Permission,
+    value::Value,
+    vm::Vm,
+};
+
+/// The `tcp` module. `tcp.listen(host, port)` returns a `tcp.Listener` whose `accept()` returns a
+/// `tcp.Stream`, the same as `tcp.connect(host, port)` does. Blocking calls stop when the VM is
+/// interrupted.
+pub fn define_natives(vm: &mut Vm) {
+    let mut tcp = vm.define_class("tcp");
+    vm.define_static_native(tcp, "listen", 2, listen);
+    vm.define_static_native(tcp, "connect", 2, connect);
+
+    let mut listener_class = new_class(vm, LISTENER_CLASS);
+    tcp.statics
+        .insert(listener_class.name, Value::Class(listener_class));
+    vm.define_method_native(listener_class, "accept", 0, listener_accept);
+    vm.define_method_native(listener_class, "port", 0, listener_port);
+    vm.define_method_native(listener_class, "close", 0, listener_close);
+
+    let mut stream_class = new_class(vm, STREAM_CLASS);
+    tcp.statics
+        .insert(stream_class.name, Value::Class(stream_class));
+    // Accepted streams find their class through the listener's
+    listener_class
+        .statics
+        .insert(stream_class.name, Value::Class(stream_class));
+    let (name, read) = vm.new_variadic_native("read", 0, stream_read);
+    stream_class.methods.insert(name, read);
+    vm.define_method_native(stream_class, "readLine", 0, stream_read_line);
+    vm.define_method_native(stream_class, "write", 1, stream_write);
+    vm.define_method_native(stream_class, "close", 0, stream_close);
+}
+
+const LISTENER_CLASS: &str = "Listener";
+const STREAM_CLASS: &str = "Stream";
+
+/// The most `read()` returns at once, and its default size
+const READ_SIZE: usize = 8192;
+
+fn new_class(vm: &mut Vm, name: &str) -> GcRef<Class> {
+    let name = vm.intern(name.to_string());
+    vm.push_root(Value::String(name));
+    let class = vm.alloc(Class::new(name));
+    vm.pop_root();
+    class
+}
+
+/// The data of a listener, which is None once closed
+struct Listener(Option<TcpListener>);
+
+/// The data of a stream, which is None once closed. Dropping the stream closes it.
+struct Stream(Option<BufReader<TcpStream>>);
+
+fn listen(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_permission(Permission::Net, None)?;
+    let address = address(vm, args[0], args[1])?;
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(err) => return net_error(vm, "listen on", &address, err),
+    };
+    // Accepting polls, so it can notice an interrupt
+    if let Err(err) = listener.set_nonblocking(true) {
+        return net_error(vm, "listen on", &address, err);
+    }
+    let class = static_class(vm, this, LISTENER_CLASS)?;
+    Ok(vm.new_host(class, Box::new(Listener(Some(listener)))))
+}
+
+fn connect(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_permission(Permission::Net, None)?;
+    let address = address(vm, args[0], args[1])?;
+    // The lookup and handshake run on their own thread, taking as long as the system allows,
+    // while this one watches for an interrupt. An abandoned attempt finishes by itself.
+    let (sender, receiver) = mpsc::channel();
+    let target = address.clone();
+    thread::spawn(move || sender.send(TcpStream::connect(target)));
+    let connected = loop {
+        match receiver.recv_timeout(Vm::POLL_INTERVAL) {
+            Ok(connected) => break connected,
+            Err(RecvTimeoutError::Timeout) => vm.check_interrupt()?,
+            Err(RecvTimeoutError::Disconnected) => {
+                break Err(io::Error::other("connection attempt failed"))
+            }
+        }
+    };
+    match connected.and_then(prepare) {
+        Ok(stream) => {
+            let class = static_class(vm, this, STREAM_CLASS)?;
+            Ok(new_stream(vm, class, stream))
+        }
+        Err(err) => net_error(vm, "connect to", &address, err),
+    }
+}
+
+/// Waits for the next connection
+fn listener_accept(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    let stream = accept(vm, this)?;
+    let class = match this {
+        Value::Host(host) => static_class(vm, Value::Class(host.class), STREAM_CLASS)?,
+        _ => unreachable!(),
+    };
+    Ok(new_stream(vm, class, stream))
+}
+
+/// The port the listener is bound to, which is how to find the one picked for port 0
+fn listener_port(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    let mut host = match this {
+        Value::Host(host) => host,
+        _ => return vm.runtime_error("Expected a listener."),
+    };
+    match host.data.downcast_mut::<Listener>() {
+        Some(Listener(Some(listener))) => match listener.local_addr() {
+            Ok(addr) => Ok(Value::Int(addr.port() as i64)),
+            Err(err) => vm.runtime_error(&format!("Could not get the port: {}.", err)),
+        },
+        Some(Listener(None)) => vm.runtime_error("Listener is closed."),
+        None => vm.runtime_error("Expected a listener."),
+    }
+}
+
+/// Stops listening. Closing twice does nothing.
+fn listener_close(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    match this {
+        Value::Host(mut host) => match host.data.downcast_mut::<Listener>() {
+            Some(Listener(listener)) => {
+                listener.take();
+                Ok(Value::Nil)
+            }
+            None => vm.runtime_error("Expected a listener."),
+        },
+        _ => vm.runtime_error("Expected a listener."),
+    }
+}
+
+/// `stream.read(size)` reads what's arrived, up to the size or `READ_SIZE`, whichever is
+/// smaller, waiting for something if nothing has. It returns nil once the other end closes the
+/// connection.
+fn stream_read(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(1, args)?;
+    let size = match args.first() {
+        None | Some(Value::Nil) => READ_SIZE,
+        Some(Value::Int(size)) if *size > 0 => (*size as usize).min(READ_SIZE),
+        Some(_) => return vm.runtime_error("Read size must be a positive integer."),
+    };
+    let mut host = stream_host(vm, this)?;
+    let stream = open_stream(vm, &mut host)?;
+    let mut buffer = vec![0; size];
+    match blocking(vm, || stream.read(&mut buffer))? {
+        Ok(0) => Ok(Value::Nil),
+        Ok(read) => {
+            let text = String::from_utf8_lossy(&buffer[..read]).into_owned();
+            Ok(Value::String(vm.intern(text)))
+        }
+        Err(err) => vm.runtime_error(&format!("Could not read from socket: {}.", err)),
+    }
+}
+
+/// Reads the next line without its line ending, or nil once the other end closes the connection
+fn stream_read_line(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    let mut host = stream_host(vm, this)?;
+    let stream = open_stream(vm, &mut host)?;
+    match read_line(vm, stream)? {
+        Ok(Some(line)) => Ok(Value::String(vm.intern(line))),
+        Ok(None) => Ok(Value::Nil),
+        Err(err) => vm.runtime_error(&format!("Could not read from socket: {}.", err)),
+    }
+}
+
+fn stream_write(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    let mut host = stream_host(vm, this)?;
+    let stream = open_stream(vm, &mut host)?;
+    match write_all(vm, stream, args[0].to_string().as_bytes())? {
+        Ok(()) => Ok(Value::Nil),
+        Err(err) => vm.runtime_error(&format!("Could not write to socket: {}.", err)),
+    }
+}
+
+/// Closes the connection now rather than when it's collected. Closing twice does nothing.
+fn stream_close(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+    let mut host = stream_host(vm, this)?;
+    match host.data.downcast_mut::<Stream>() {
+        Some(Stream(stream)) => {
+            stream.take();
+            Ok(Value::Nil)
+        }
+        None => unreachable!(),
+    }
+}
+
+/// Waits for the next connection to a `tcp.Listener`
+pub fn accept(vm: &mut Vm, listener: Value) -> Result<TcpStream> {
+    let mut host = match listener {
+        Value::Host(host) => host,
+        _ => return vm.runtime_error("Expected a listener."),
+    };
+    let listener = match host.data.downcast_mut::<Listener>() {
+        Some(Listener(Some(listener))) => listener,
+        Some(Listener(None)) => return vm.runtime_error("Listener is closed."),
+        None => return vm.runtime_error("Expected a listener."),
+    };
+    let accepted = blocking(vm, || {
+        let accepted = listener.accept();
+        if matches!(&accepted, Err(err) if err.kind() == ErrorKind::WouldBlock) {
+            thread::sleep(Vm::POLL_INTERVAL);
+        }
+        accepted
+    })?;
+    match accepted.and_then(|(stream, _)| prepare(stream)) {
+        Ok(stream) => Ok(stream),
+        Err(err) => vm.runtime_error(&format!("Could not accept a connection: {}.", err)),
+    }
+}
+
+/// Reads a line without its line ending, or None at the end of the stream
+pub fn read_line(vm: &Vm, stream: &mut BufReader<TcpStream>) -> Result<io::Result<Option<String>>> {
+    let mut line = vec![];
+    // Bytes read before a timeout stay in the line, so carrying on where it stopped loses nothing
+    Ok(
+        blocking(vm, || stream.read_until(b'\n', &mut line))?.map(|read| {
+            if read == 0 && line.is_empty() {
+                return None;
+            }
+            let line = line.strip_suffix(b"\n").unwrap_or(&line);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            Some(String::from_utf8_lossy(line).into_owned())
+        }),
+    )
+}
+
+/// Reads exactly as many bytes as the buffer holds
+pub fn read_exact(
+    vm: &Vm,
+    stream: &mut BufReader<TcpStream>,
+    buffer: &mut [u8],
+) -> Result<io::Result<()>> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match blocking(vm, || stream.read(&mut buffer[filled..]))? {
+            Ok(0) => return Ok(Err(ErrorKind::UnexpectedEof.into())),
+            Ok(read) => filled += read,
+            Err(err) => return Ok(Err(err)),
+        }
+    }
+    Ok(Ok(()))
+}
+
+pub fn write_all(
+    vm: &Vm,
+    stream: &mut BufReader<TcpStream>,
+    bytes: &[u8],
+) -> Result<io::Result<()>> {
+    let mut written = 0;
+    while written < bytes.len() {
+        match blocking(vm, || stream.get_mut().write(&bytes[written..]))? {
+            Ok(0) => return Ok(Err(ErrorKind::WriteZero.into())),
+            Ok(count) => written += count,
+            Err(err) => return Ok(Err(err)),
+        }
+    }
+    Ok(Ok(()))
+}
+
+/// Sockets time out after `Vm::POLL_INTERVAL`, so the calls that wait on them can check
+fn prepare(stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Vm::POLL_INTERVAL))?;
+    stream.set_write_timeout(Some(Vm::POLL_INTERVAL))?;
+    Ok(stream)
+}
+
+/// Repeats an operation on a socket for as long as it times out, unless the VM is interrupted
+fn blocking<T>(vm: &Vm, mut operation: impl FnMut() -> io::Result<T>) -> Result<io::Result<T>> {
+    loop {
+        match operation() {
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) =>
+            {
+                vm.check_interrupt()?
+            }
+            result => return Ok(result),
+        }
+    }
+}
+
+fn new_stream(vm: &mut Vm, class: GcRef<Class>, stream: TcpStream) -> Value {
+    vm.new_host(class, Box::new(Stream(Some(BufReader::new(stream)))))
+}
+
+fn stream_host(vm: &Vm, this: Value) -> Result<GcRef<Host>> {
+    match this {
+        Value::Host(host) if host.data.is::<Stream>() => Ok(host),
+        _ => vm.runtime_error("Expected a stream."),
+    }
+}
+
+fn open_stream<'a>(vm: &Vm, host: &'a mut GcRef<Host>) -> Result<&'a mut BufReader<TcpStream>> {
+    match host.data.downcast_mut::<Stream>() {
+        Some(Stream(Some(stream))) => Ok(stream),
+        _ => vm.runtime_error("Stream is closed."),
+    }
+}
+
+/// A class from the statics of the given one
+fn static_class(vm: &mut Vm, class: Value, name: &str) -> Result<GcRef<Class>> {
+    let name = vm.intern(name.to_string());
+    match class {
+        Value::Class(class) => match class.statics.get(name) {
+            Some(Value::Class(class)) => Ok(class),
+            _ => vm.runtime_error(&format!("tcp.{} must be a class.", name.as_str())),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn address(vm: &Vm, host: Value, port: Value) -> Result<String> {
+    let host = match host {
+        Value::String(host) => host,
+        _ => return vm.runtime_error("Host must be a string."),
+    };
+    match port {
+        Value::Int(port) if (0..=u16::MAX as i64).contains(&port) => {
+            Ok(format_address(host, port as u16))
+        }
+        _ => vm.runtime_error("Port must be an integer from 0 to 65535."),
+    }
+}
+
+/// IPv6 addresses go in brackets so their colons aren't mistaken for the port's
+fn format_address(host: GcRef<LoxString>, port: u16) -> String {
+    if host.as_str().contains(':') {
+        format!("[{}]:{}", host.as_str(), port)
+    } else {
+        format!("{}:{}", host.as_str(), port)
+    }
+}
+
+fn net_error<T>(vm: &Vm, action: &str, address: &str, err: io::Error) -> Result<T> {
+    vm.runtime_error(&format!("Could not {} {}: {}.", action, address, err))
+}
@@ -0,0 +1,719 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::Result,
+    gc::GcRef,
+    obj::{Class, Instance, LoxString, NativeFn},
+    value::Value,
+    vm::Vm,
+};
+
+/// The `time` module. Times are numbers of seconds since the Unix epoch, like `clock()`, and are
+/// shown in UTC or at a fixed offset such as "+05:30".
+pub fn define_natives(vm: &mut Vm) {
+    let mut time = vm.define_class("time");
+    vm.define_static_native(time, "now", 0, now);
+    vm.define_static_native(time, "monotonic", 0, monotonic);
+    vm.define_static_native(time, "sleep", 1, sleep);
+    for (name, min_arity, function) in [
+        ("format", 1, format as NativeFn),
+        ("parse", 1, parse),
+        ("date", 3, date),
+        ("parts", 1, parts),
+        ("addMonths", 2, add_months),
+        ("addYears", 2, add_years),
+    ] {
+        let (name, native) = vm.new_variadic_native(name, min_arity, function);
+        time.statics.insert(name, native);
+    }
+
+    let parts_name = vm.intern(PARTS_CLASS.to_string());
+    vm.push_root(Value::String(parts_name));
+    let parts_class = vm.alloc(Class::new(parts_name));
+    vm.pop_root();
+    time.statics.insert(parts_name, Value::Class(parts_class));
+}
+
+/// The class of the instances returned by `time.parts()`
+const PARTS_CLASS: &str = "Parts";
+
+/// Where scripts get the time from
+pub enum Clock {
+    /// The system clocks, with monotonic time counted from when the VM started
+    System { start: Instant },
+    /// A time supplied by the embedder, which only moves when it's set or a script sleeps, so
+    /// runs are repeatable
+    Manual { now: f64, monotonic: f64 },
+}
+
+impl Clock {
+    pub fn system() -> Self {
+        Clock::System {
+            start: Instant::now(),
+        }
+    }
+
+    pub fn manual(now: f64) -> Self {
+        Clock::Manual {
+            now,
+            monotonic: 0.0,
+        }
+    }
+
+    /// Seconds since the Unix epoch
+    pub fn now(&self) -> f64 {
+        match self {
+            Clock::System { .. } => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+            Clock::Manual { now, .. } => *now,
+        }
+    }
+
+    /// Seconds since an arbitrary point, which never go backwards
+    pub fn monotonic(&self) -> f64 {
+        match self {
+            Clock::System { start } => start.elapsed().as_secs_f64(),
+            Clock::Manual { monotonic, .. } => *monotonic,
+        }
+    }
+
+    /// Waits, or with a manual clock moves the time forward instead. Waiting stops early once
+    /// `interrupted` is set, which is the only way out of a wait too long for an `Instant`.
+    pub fn sleep(&mut self, seconds: f64, interrupted: &AtomicBool) {
+        match self {
+            Clock::System { .. } => {
+                let end = Duration::try_from_secs_f64(seconds)
+                    .ok()
+                    .and_then(|duration| Instant::now().checked_add(duration));
+                while !interrupted.load(Ordering::Relaxed) {
+                    let left = match end {
+                        Some(end) => end.saturating_duration_since(Instant::now()),
+                        None => Vm::POLL_INTERVAL,
+                    };
+                    if left.is_zero() {
+                        break;
+                    }
+                    thread::sleep(left.min(Vm::POLL_INTERVAL));
+                }
+            }
+            Clock::Manual { now, monotonic } => {
+                *now += seconds;
+                *monotonic += seconds;
+            }
+        }
+    }
+}
+
+fn now(vm: &mut Vm, _this: Value, _args: &[Value]) -> Result<Value> {
+    Ok(Value::Number(vm.clock.now()))
+}
+
+fn monotonic(vm: &mut Vm, _this: Value, _args: &[Value]) -> Result<Value> {
+    Ok(Value::Number(vm.clock.monotonic()))
+}
+
+fn sleep(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    match args[0].as_f64() {
+        Some(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+            let interrupted = vm.interrupt_handle();
+            vm.clock.sleep(seconds, &interrupted);
+            vm.check_interrupt()?;
+            Ok(Value::Nil)
+        }
+        _ => vm.runtime_error("Sleep duration must be a non-negative number."),
+    }
+}
+
+/// `time.format(time, pattern, offset)`, where the pattern defaults to ISO 8601 and the offset
+/// to UTC
+fn format(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(3, args)?;
+    let time = time_arg(vm, args[0])?;
+    let offset = offset_arg(vm, args.get(2))?;
+    let pattern = match args.get(1) {
+        None | Some(Value::Nil) => None,
+        Some(Value::String(pattern)) => Some(*pattern),
+        Some(_) => return vm.runtime_error("Time pattern must be a string."),
+    };
+    let parts = Parts::from_time(time, offset);
+    let formatted = match pattern {
+        Some(pattern) => match parts.format(pattern.as_str()) {
+            Ok(formatted) => formatted,
+            Err(message) => return vm.runtime_error(&message),
+        },
+        None => parts.to_iso(),
+    };
+    Ok(Value::String(vm.intern(formatted)))
+}
+
+/// `time.parse(text, pattern)`, where text without a pattern is ISO 8601. Text without an offset
+/// is read as UTC.
+fn parse(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(2, args)?;
+    let text = match args[0] {
+        Value::String(text) => text,
+        _ => return vm.runtime_error("Time text must be a string."),
+    };
+    let result = match args.get(1) {
+        None | Some(Value::Nil) => Parts::parse_iso(text.as_str()),
+        Some(Value::String(pattern)) => Parts::parse(text.as_str(), pattern.as_str()),
+        Some(_) => return vm.runtime_error("Time pattern must be a string."),
+    };
+    match result {
+        Ok(parts) => Ok(Value::Number(parts.to_time())),
+        Err(reason) => vm.runtime_error(&format!(
+            "Could not parse '{}' as a time: {}.",
+            text.as_str(),
+            reason
+        )),
+    }
+}
+
+/// `time.date(year, month, day, hour, minute, second, offset)`, where the time of day defaults
+/// to midnight and the offset to UTC
+fn date(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(7, args)?;
+    let mut fields = [0i64; 5];
+    for (i, field) in fields.iter_mut().enumerate() {
+        *field = match args.get(i) {
+            Some(Value::Int(x)) => *x,
+            None => 0,
+            Some(_) => return vm.runtime_error("Date fields must be integers."),
+        };
+    }
+    let [year, month, day, hour, minute] = fields;
+    let second = match args.get(5) {
+        None => 0.0,
+        Some(second) => match second.as_f64() {
+            Some(second) => second,
+            None => return vm.runtime_error("Seconds must be a number."),
+        },
+    };
+    let offset = offset_arg(vm, args.get(6))?;
+    let parts = Parts {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second: second.trunc() as i64,
+        millisecond: ((second - second.trunc()) * 1000.0).round() as i64,
+        offset,
+    };
+    match parts.validate() {
+        Ok(()) => Ok(Value::Number(parts.to_time())),
+        Err(reason) => vm.runtime_error(&format!("Invalid date: {}.", reason)),
+    }
+}
+
+/// `time.parts(time, offset)`, the calendar fields of a time as a `time.Parts` instance
+fn parts(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(2, args)?;
+    let time = time_arg(vm, args[0])?;
+    let offset = offset_arg(vm, args.get(1))?;
+    let parts = Parts::from_time(time, offset);
+
+    let parts_name = vm.intern(PARTS_CLASS.to_string());
+    let class = match this {
+        Value::Class(time_class) => match time_class.statics.get(parts_name) {
+            Some(Value::Class(class)) => class,
+            _ => return vm.runtime_error("time.Parts must be a class."),
+        },
+        _ => unreachable!(),
+    };
+    let mut instance = vm.alloc(Instance::new(class));
+    // Keep the instance reachable while its field names are allocated
+    vm.push_root(Value::Instance(instance));
+    let days = days_from_civil(parts.year, parts.month, parts.day);
+    let fields = [
+        ("year", parts.year),
+        ("month", parts.month),
+        ("day", parts.day),
+        ("hour", parts.hour),
+        ("minute", parts.minute),
+        ("second", parts.second),
+        ("millisecond", parts.millisecond),
+        // Monday is 1 and Sunday is 7, as in ISO 8601
+        ("weekday", (days + 3).rem_euclid(7) + 1),
+        ("yearDay", days - days_from_civil(parts.year, 1, 1) + 1),
+    ];
+    for (name, value) in fields {
+        let name = vm.intern(name.to_string());
+        instance.fields.insert(name, Value::Int(value));
+    }
+    let name = vm.intern("offset".to_string());
+    vm.push_root(Value::String(name));
+    let offset = vm.intern(format_offset(parts.offset, true));
+    vm.pop_root();
+    instance.fields.insert(name, Value::String(offset));
+    vm.pop_root();
+    Ok(Value::Instance(instance))
+}
+
+/// `time.addMonths(time, months, offset)` moves the calendar date at the offset, keeping the time
+/// of day. Days past the end of the new month become its last day.
+fn add_months(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(3, args)?;
+    let months = match args[1] {
+        Value::Int(months) => months,
+        _ => return vm.runtime_error("Months must be an integer."),
+    };
+    shift_months(vm, args[0], months, args.get(2))
+}
+
+/// `time.addYears(time, years, offset)`, where the 29th of February becomes the 28th in years
+/// that aren't leap years
+fn add_years(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+    vm.check_max_arity(3, args)?;
+    let months = match args[1] {
+        Value::Int(years) => years.saturating_mul(12),
+        _ => return vm.runtime_error("Years must be an integer."),
+    };
+    shift_months(vm, args[0], months, args.get(2))
+}
+
+fn shift_months(vm: &Vm, time: Value, months: i64, offset: Option<&Value>) -> Result<Value> {
+    let time = time_arg(vm, time)?;
+    let offset = offset_arg(vm, offset)?;
+    let mut parts = Parts::from_time(time, offset);
+    let month_index = (parts.year * 12 + parts.month - 1).saturating_add(months);
+    parts.year = month_index.div_euclid(12);
+    parts.month = month_index.rem_euclid(12) + 1;
+    parts.day = parts.day.min(days_in_month(parts.year, parts.month));
+    match parts.validate() {
+        Ok(()) => Ok(Value::Number(parts.to_time())),
+        Err(reason) => vm.runtime_error(&format!("Invalid date: {}.", reason)),
+    }
+}
+
+/// A time in seconds, limited to the years -9999 to 9999 like `time.date`
+fn time_arg(vm: &Vm, value: Value) -> Result<f64> {
+    let min = (days_from_civil(-9999, 1, 1) * SECONDS_PER_DAY) as f64;
+    let max = (days_from_civil(10000, 1, 1) * SECONDS_PER_DAY) as f64;
+    match value.as_f64() {
+        Some(time) if (min..max).contains(&time) => Ok(time),
+        Some(time) if time.is_finite() => {
+            vm.runtime_error("Time must be within the years -9999 to 9999.")
+        }
+        _ => vm.runtime_error("Time must be a number of seconds."),
+    }
+}
+
+/// An offset east of UTC in minutes, from a string like "Z", "+02:00" or "-0330"
+fn offset_arg(vm: &Vm, value: Option<&Value>) -> Result<i64> {
+    match value {
+        None | Some(Value::Nil) => Ok(0),
+        Some(Value::String(offset)) => match parse_offset(offset.as_str()) {
+            Some(offset) => Ok(offset),
+            None => invalid_offset(vm, *offset),
+        },
+        Some(_) => vm.runtime_error("Offset must be a string like \"+02:00\"."),
+    }
+}
+
+fn invalid_offset<T>(vm: &Vm, offset: GcRef<LoxString>) -> Result<T> {
+    vm.runtime_error(&format!(
+        "Invalid offset '{}', expected one like \"+02:00\".",
+        offset.as_str()
+    ))
+}
+
+fn parse_offset(offset: &str) -> Option<i64> {
+    if offset == "Z" {
+        return Some(0);
+    }
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = offset[1..].replacen(':', "", 1);
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Like "+05:30", or "+0530" without the colon
+fn format_offset(offset: i64, colon: bool) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let (hours, minutes) = (offset.abs() / 60, offset.abs() % 60);
+    if colon {
+        format!("{}{:02}:{:02}", sign, hours, minutes)
+    } else {
+        format!("{}{:02}{:02}", sign, hours, minutes)
+    }
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// A time on the calendar, as seen at an offset from UTC
+#[derive(Debug, PartialEq)]
+struct Parts {
+    year: i64,
+    /// 1 to 12
+    month: i64,
+    /// 1 to 31
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    millisecond: i64,
+    /// Minutes east of UTC
+    offset: i64,
+}
+
+impl Parts {
+    /// Times are rounded to the millisecond
+    fn from_time(time: f64, offset: i64) -> Self {
+        let millis = (time * 1000.0).round() as i64 + offset * 60 * 1000;
+        let seconds = millis.div_euclid(1000);
+        let days = seconds.div_euclid(SECONDS_PER_DAY);
+        let second_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Parts {
+            year,
+            month,
+            day,
+            hour: second_of_day / 3600,
+            minute: second_of_day % 3600 / 60,
+            second: second_of_day % 60,
+            millisecond: millis.rem_euclid(1000),
+            offset,
+        }
+    }
+
+    fn to_time(&self) -> f64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let seconds = days * SECONDS_PER_DAY + self.hour * 3600 + self.minute * 60 + self.second
+            - self.offset * 60;
+        seconds as f64 + self.millisecond as f64 / 1000.0
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        let check = |value: i64, min: i64, max: i64, name: &str| {
+            if (min..=max).contains(&value) {
+                Ok(())
+            } else {
+                Err(format!("{} must be from {} to {}", name, min, max))
+            }
+        };
+        check(self.year, -9999, 9999, "year")?;
+        check(self.month, 1, 12, "month")?;
+        check(
+            self.day,
+            1,
+            days_in_month(self.year, self.month),
+            "day of the month",
+        )?;
+        check(self.hour, 0, 23, "hour")?;
+        check(self.minute, 0, 59, "minute")?;
+        check(self.second, 0, 59, "second")?;
+        check(self.millisecond, 0, 999, "millisecond")
+    }
+
+    /// Like "2024-03-15T09:30:00+00:00", with milliseconds only when there are some
+    fn to_iso(&self) -> String {
+        let mut iso = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        if self.millisecond != 0 {
+            iso.push_str(&format!(".{:03}", self.millisecond));
+        }
+        iso.push_str(&format_offset(self.offset, true));
+        iso
+    }
+
+    /// Formats with strftime-style directives: %Y %m %d %H %M %S, %f for milliseconds, %j for the
+    /// day of the year, %a %A %b %B for names, %z and %:z for the offset, and %% for '%'
+    fn format(&self, pattern: &str) -> std::result::Result<String, String> {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let weekday = WEEKDAYS[(days + 3).rem_euclid(7) as usize];
+        let month = MONTHS[self.month as usize - 1];
+        let mut output = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => output.push_str(&format!("{:04}", self.year)),
+                Some('m') => output.push_str(&format!("{:02}", self.month)),
+                Some('d') => output.push_str(&format!("{:02}", self.day)),
+                Some('H') => output.push_str(&format!("{:02}", self.hour)),
+                Some('M') => output.push_str(&format!("{:02}", self.minute)),
+                Some('S') => output.push_str(&format!("{:02}", self.second)),
+                Some('f') => output.push_str(&format!("{:03}", self.millisecond)),
+                Some('j') => {
+                    let year_day = days - days_from_civil(self.year, 1, 1) + 1;
+                    output.push_str(&format!("{:03}", year_day))
+                }
+                Some('a') => output.push_str(&weekday[..3]),
+                Some('A') => output.push_str(weekday),
+                Some('b') => output.push_str(&month[..3]),
+                Some('B') => output.push_str(month),
+                Some('z') => output.push_str(&format_offset(self.offset, false)),
+                Some(':') if chars.next() == Some('z') => {
+                    output.push_str(&format_offset(self.offset, true))
+                }
+                Some('%') => output.push('%'),
+                _ => return Err(format!("Invalid directive in time pattern '{}'.", pattern)),
+            }
+        }
+        Ok(output)
+    }
+
+    /// Reads text written with the directives of `format`. Fields that aren't in the pattern
+    /// default to the start of 1970 in UTC.
+    fn parse(text: &str, pattern: &str) -> std::result::Result<Self, String> {
+        let mut parts = Parts::from_time(0.0, 0);
+        let mut text = text;
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text = text
+                    .strip_prefix(c)
+                    .ok_or_else(|| format!("expected '{}'", c))?;
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => {
+                    let negative = text.starts_with('-');
+                    let (year, rest) = take_number(text.trim_start_matches(['-', '+']), 4)?;
+                    parts.year = if negative { -year } else { year };
+                    text = rest;
+                }
+                Some('m') => (parts.month, text) = take_number(text, 2)?,
+                Some('d') => (parts.day, text) = take_number(text, 2)?,
+                Some('H') => (parts.hour, text) = take_number(text, 2)?,
+                Some('M') => (parts.minute, text) = take_number(text, 2)?,
+                Some('S') => (parts.second, text) = take_number(text, 2)?,
+                Some('f') => {
+                    let digits = text.chars().take_while(char::is_ascii_digit).count();
+                    if digits == 0 {
+                        return Err("expected a number".to_string());
+                    }
+                    // Only milliseconds are kept, so further digits are dropped
+                    let mut millis = text[..digits.min(3)].to_string();
+                    while millis.len() < 3 {
+                        millis.push('0');
+                    }
+                    parts.millisecond = millis.parse().unwrap();
+                    text = &text[digits..];
+                }
+                Some('a') => text = take_name(text, &WEEKDAYS, 3)?.1,
+                Some('A') => text = take_name(text, &WEEKDAYS, 0)?.1,
+                Some('b') => (parts.month, text) = take_name(text, &MONTHS, 3)?,
+                Some('B') => (parts.month, text) = take_name(text, &MONTHS, 0)?,
+                Some('z') => (parts.offset, text) = take_offset(text)?,
+                Some(':') if chars.next() == Some('z') => (parts.offset, text) = take_offset(text)?,
+                Some('%') => {
+                    text = text
+                        .strip_prefix('%')
+                        .ok_or_else(|| "expected '%'".to_string())?
+                }
+                _ => return Err(format!("invalid directive in pattern '{}'", pattern)),
+            }
+        }
+        if !text.is_empty() {
+            return Err(format!("unexpected '{}' at the end", text));
+        }
+        parts.validate()?;
+        Ok(parts)
+    }
+
+    /// Reads a date like "2024-03-15", or a date and time like "2024-03-15T09:30:00.250+02:00"
+    /// where the seconds, milliseconds and offset are optional
+    fn parse_iso(text: &str) -> std::result::Result<Self, String> {
+        let (date, time) = match text.find(['T', ' ']) {
+            Some(index) => (&text[..index], Some(&text[index + 1..])),
+            None => (text, None),
+        };
+        let time = match time {
+            None => return Parts::parse(date, "%Y-%m-%d"),
+            Some(time) => time,
+        };
+        let (time, offset) = match time.find(['Z', '+', '-']) {
+            Some(index) => (&time[..index], Some(&time[index..])),
+            None => (time, None),
+        };
+        let time_pattern = match time.len() {
+            5 => "%H:%M",
+            8 => "%H:%M:%S",
+            _ => "%H:%M:%S.%f",
+        };
+        let offset_pattern = if offset.is_some() { "%z" } else { "" };
+        let pattern = format!("%Y-%m-%dT{}{}", time_pattern, offset_pattern);
+        let text = format!("{}T{}{}", date, time, offset.unwrap_or_default());
+        Parts::parse(&text, &pattern)
+    }
+}
+
+/// A number of up to `max_digits` digits from the start of the text, and the rest of the text
+fn take_number(text: &str, max_digits: usize) -> std::result::Result<(i64, &str), String> {
+    let digits = text
+        .chars()
+        .take(max_digits)
+        .take_while(char::is_ascii_digit)
+        .count();
+    if digits == 0 {
+        return Err("expected a number".to_string());
+    }
+    Ok((text[..digits].parse().unwrap(), &text[digits..]))
+}
+
+/// The number, counting from 1, of the name at the start of the text, matched without regard to
+/// case. A length shortens the names to abbreviations.
+fn take_name<'a>(
+    text: &'a str,
+    names: &[&str],
+    length: usize,
+) -> std::result::Result<(i64, &'a str), String> {
+    for (i, name) in names.iter().enumerate() {
+        let name = if length > 0 { &name[..length] } else { name };
+        if let Some(start) = text.get(..name.len()) {
+            if start.eq_ignore_ascii_case(name) {
+                return Ok((i as i64 + 1, &text[name.len()..]));
+            }
+        }
+    }
+    Err("expected a name".to_string())
+}
+
+fn take_offset(text: &str) -> std::result::Result<(i64, &str), String> {
+    // "Z", "+0200" or "+02:00"
+    for length in [1, 5, 6] {
+        if let Some(offset) = text.get(..length).and_then(parse_offset) {
+            return Ok((offset, &text[length..]));
+        }
+    }
+    Err("expected an offset".to_string())
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar, from Howard Hinnant's algorithm
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The year, month and day of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_round_trip() {
+        for days in -800_000..800_000 {
+            let (year, month, day) = civil_from_days(days);
+            assert!((1..=days_in_month(year, month)).contains(&day));
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19797), (2024, 3, 15));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn format_and_parse() {
+        let parts = Parts::from_time(1710495000.25, 330);
+        assert_eq!(parts.to_iso(), "2024-03-15T15:00:00.250+05:30");
+        assert_eq!(Parts::parse_iso(&parts.to_iso()), Ok(parts));
+
+        let parts = Parts::parse(
+            "Fri, 15 Mar 2024 09:30:00 +0000",
+            "%a, %d %b %Y %H:%M:%S %z",
+        );
+        assert_eq!(parts.map(|parts| parts.to_time()), Ok(1710495000.0));
+        assert!(Parts::parse("2024-02-30", "%Y-%m-%d").is_err());
+    }
+
+    #[test]
+    fn manual_clock() {
+        let mut clock = Clock::manual(100.0);
+        clock.sleep(2.5, &AtomicBool::new(false));
+        assert_eq!(clock.now(), 102.5);
+        assert_eq!(clock.monotonic(), 2.5);
+    }
+
+    #[test]
+    fn endless_sleep() {
+        // Too long for a Duration or an Instant, so only an interrupt ends it
+        let interrupted = AtomicBool::new(true);
+        let mut clock = Clock::system();
+        clock.sleep(1e300, &interrupted);
+        clock.sleep(1e19, &interrupted);
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    cmp::Ordering,
     fmt,
     fmt::{Debug, Display, Formatter},
     ops::Deref,
@@ -6,14 +7,19 @@ use std::{
 
 use crate::{
     gc::{GarbageCollect, Gc, GcRef},
-    obj::{BoundMethod, Class, Closure, Function, Instance, LoxString, NativeFunction},
+    obj::{
+        BoundMethod, Class, Closure, Fiber, Function, Host, Instance, List, LoxString,
+        NativeFunction,
+    },
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub enum Value {
     Bool(bool),
+    #[default]
     Nil,
     Number(f64),
+    Int(i64),
     // Following are pointers to garbage collected objects. Value is NOT deep copied.
     String(GcRef<LoxString>),
     Function(GcRef<Function>),
@@ -22,6 +28,9 @@ pub enum Value {
     Class(GcRef<Class>),
     Instance(GcRef<Instance>),
     BoundMethod(GcRef<BoundMethod>),
+    Fiber(GcRef<Fiber>),
+    List(GcRef<List>),
+    Host(GcRef<Host>),
 }
 
 impl Value {
@@ -32,13 +41,45 @@ impl Value {
             _ => false,
         }
     }
+
+    /// The value as a float, if it is numeric. Ints are promoted to floats when mixed with them.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(x) => Some(*x),
+            Value::Int(x) => Some(*x as f64),
+            _ => None,
+        }
+    }
+
+    /// Compare two numeric values exactly, even where an int can't be represented as a float.
+    /// Returns None for non-numeric values and NaN.
+    pub fn compare_numbers(&self, other: &Self) -> Option<Ordering> {
+        match (*self, *other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(&b)),
+            (Value::Int(a), Value::Number(b)) => compare_int_float(a, b),
+            (Value::Number(a), Value::Int(b)) => compare_int_float(b, a).map(Ordering::reverse),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
+    }
+}
+
+fn compare_int_float(a: i64, b: f64) -> Option<Ordering> {
+    // i64::MAX as f64 rounds up to 2^63, which is outside the range of i64
+    if b.fract() == 0.0 && b >= i64::MIN as f64 && b < i64::MAX as f64 {
+        Some(a.cmp(&(b as i64)))
+    } else {
+        (a as f64).partial_cmp(&b)
+    }
 }
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Bool(a), Value::Bool(b)) => a == b,
-            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Number(_) | Value::Int(_), Value::Number(_) | Value::Int(_)) => {
+                self.compare_numbers(other) == Some(Ordering::Equal)
+            }
             (Value::Nil, Value::Nil) => true,
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Function(a), Value::Function(b)) => a == b,
@@ -47,6 +88,9 @@ impl PartialEq for Value {
             (Value::Class(a), Value::Class(b)) => a == b,
             (Value::Instance(a), Value::Instance(b)) => a == b,
             (Value::BoundMethod(a), Value::BoundMethod(b)) => a == b,
+            (Value::Fiber(a), Value::Fiber(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Host(a), Value::Host(b)) => a == b,
             _ => false,
         }
     }
@@ -58,6 +102,7 @@ impl Display for Value {
             Value::Bool(x) => Display::fmt(&x, f),
             Value::Nil => f.write_str("nil"),
             Value::Number(x) => Display::fmt(&x, f),
+            Value::Int(x) => Display::fmt(&x, f),
             Value::String(x) => Display::fmt(x.deref(), f),
             Value::Function(x) => Display::fmt(x.deref(), f),
             Value::NativeFunction(x) => Display::fmt(x.deref(), f),
@@ -65,6 +110,9 @@ impl Display for Value {
             Value::Class(x) => Display::fmt(x.deref(), f),
             Value::Instance(x) => Display::fmt(x.deref(), f),
             Value::BoundMethod(x) => Display::fmt(x.deref(), f),
+            Value::Fiber(x) => Display::fmt(x.deref(), f),
+            Value::List(x) => Display::fmt(x.deref(), f),
+            Value::Host(x) => Display::fmt(x.deref(), f),
         }
     }
 }
@@ -75,12 +123,6 @@ impl Debug for Value {
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Self::Nil
-    }
-}
-
 impl GarbageCollect for Value {
     fn mark_gray(&mut self, gc: &mut Gc) {
         match self {
@@ -88,7 +130,13 @@ impl GarbageCollect for Value {
             Value::Function(x) => x.mark_gray(gc),
             Value::NativeFunction(x) => x.mark_gray(gc),
             Value::Closure(x) => x.mark_gray(gc),
-            _ => {}
+            Value::Class(x) => x.mark_gray(gc),
+            Value::Instance(x) => x.mark_gray(gc),
+            Value::BoundMethod(x) => x.mark_gray(gc),
+            Value::Fiber(x) => x.mark_gray(gc),
+            Value::List(x) => x.mark_gray(gc),
+            Value::Host(x) => x.mark_gray(gc),
+            Value::Bool(_) | Value::Nil | Value::Number(_) | Value::Int(_) => {}
         }
     }
 }
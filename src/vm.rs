@@ -1,82 +1,164 @@
 use std::{
+    any::Any,
+    cell::RefCell,
+    cmp::Ordering,
     fmt::Display,
     ptr::null,
-    time::{SystemTime, UNIX_EPOCH},
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use crate::{
     error::{LoxError, Result},
+    file, format,
     gc::{GarbageCollect, Gc, GcRef},
+    http, json,
     obj::{
-        BoundMethod, Class, Closure, FunctionUpvalue, Instance, LoxString, NativeFn,
-        NativeFunction, Upvalue,
+        BoundMethod, Class, Closure, Fiber, FunctionUpvalue, Host, Instance, List, LoxString,
+        NativeFn, NativeFunction, Upvalue,
     },
     op_code::{Constant, Invoke, Jump, LocalIndex},
-    parser,
+    os, parser,
+    permissions::{Permission, Permissions},
+    process, reflect, regex,
     stack::Stack,
     table::Table,
+    tcp,
+    time::{self, Clock},
 };
 
 use crate::{op_code::OpCode, value::Value};
 
 pub type ValueStack = Stack<Value, { Vm::STACK_MAX }>;
+pub type CallFrameStack = Stack<CallFrame, { Vm::FRAMES_MAX }>;
 pub struct Vm {
     pub gc: Gc,
-    stack: ValueStack,
-    frames: Stack<CallFrame, { Vm::FRAMES_MAX }>,
+    /// The fiber currently executing
+    fiber: GcRef<Fiber>,
+    /// The fiber top-level code runs in
+    root_fiber: GcRef<Fiber>,
     globals: Table,
-    open_upvalues: Option<GcRef<Upvalue>>,
+    /// The names of globals declared with `const`
+    const_globals: Table,
     init_string: GcRef<LoxString>,
+    /// The fields every enum variant has
+    name_string: GcRef<LoxString>,
+    ordinal_string: GcRef<LoxString>,
+    fiber_class: GcRef<Class>,
+    list_class: GcRef<Class>,
+    /// How many calls from Rust back into Lox are currently running, see `call_function`
+    nested_calls: usize,
+    /// The message and callstack of the runtime error being raised
+    pending_error: RefCell<Option<(String, String)>>,
+    /// Where `clock()` and the `time` module get the time from
+    pub clock: Clock,
+    /// What scripts may touch outside the VM
+    permissions: Permissions,
+    /// Set from any thread to stop the running script, see `interrupt_handle`
+    interrupted: Arc<AtomicBool>,
 }
 
 impl Vm {
     const FRAMES_MAX: usize = 64;
     const STACK_MAX: usize = Self::FRAMES_MAX * (u8::MAX as usize + 1);
+    /// How often natives that block, like `time.sleep` or reading a socket, check for an interrupt
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
-    pub fn new() -> Vm {
+    pub fn new(permissions: Permissions) -> Vm {
         let mut gc = Gc::new();
         let init_string = gc.intern("init".to_string());
+        let name_string = gc.intern("name".to_string());
+        let ordinal_string = gc.intern("ordinal".to_string());
+        let root_fiber = gc.alloc(Fiber::new());
+        let fiber_name = gc.intern("Fiber".to_string());
+        let mut fiber_class = gc.alloc(Class::new(fiber_name));
+        let list_name = gc.intern("List".to_string());
+        let list_class = gc.alloc(Class::new(list_name));
 
         let mut vm = Vm {
             gc,
-            stack: Stack::new(),
-            frames: Stack::new(),
+            fiber: root_fiber,
+            root_fiber,
             globals: Table::new(),
-            open_upvalues: None,
+            const_globals: Table::new(),
             init_string,
+            name_string,
+            ordinal_string,
+            fiber_class,
+            list_class,
+            nested_calls: 0,
+            pending_error: RefCell::new(None),
+            clock: Clock::system(),
+            permissions,
+            interrupted: Arc::new(AtomicBool::new(false)),
         };
 
-        vm.define_native("clock", |_| {
-            Value::Number(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs_f64(),
-            )
-        });
+        vm.define_native("clock", 0, |vm, _, _| Ok(Value::Number(vm.clock.now())));
+
+        vm.define_variadic_native("try", 1, Vm::try_native);
+
+        vm.globals.insert(fiber_name, Value::Class(fiber_class));
+        vm.define_static_native(fiber_class, "new", 1, Vm::fiber_new);
+        // The value passed through resume() and yield() is optional
+        let (name, native) = vm.new_variadic_native("yield", 0, Vm::fiber_yield);
+        fiber_class.statics.insert(name, native);
+        let (name, native) = vm.new_variadic_native("resume", 0, Vm::fiber_resume);
+        fiber_class.methods.insert(name, native);
+        vm.define_method_native(fiber_class, "isDone", 0, Vm::fiber_is_done);
+
+        vm.globals.insert(list_name, Value::Class(list_class));
+        vm.define_method_native(list_class, "push", 1, Vm::list_push);
+        vm.define_method_native(list_class, "pop", 0, Vm::list_pop);
+        vm.define_method_native(list_class, "len", 0, Vm::list_len);
+
+        reflect::define_natives(&mut vm);
+        file::define_natives(&mut vm);
+        os::define_natives(&mut vm);
+        json::define_natives(&mut vm);
+        format::define_natives(&mut vm);
+        regex::define_natives(&mut vm);
+        time::define_natives(&mut vm);
+        process::define_natives(&mut vm);
+        tcp::define_natives(&mut vm);
+        http::define_natives(&mut vm);
 
         vm
     }
 
     pub fn interpret(&mut self, source: &str) -> Result<()> {
+        // An interrupt only stops the script that was running when it came
+        self.interrupted.store(false, AtomicOrdering::Relaxed);
         let function = parser::compile(source, &mut self.gc)?;
         // Leave the <script> function on the stack forever so it's not GC'd
-        self.stack.push(Value::Function(function));
+        self.fiber.stack.push(Value::Function(function));
         let closure = Closure::new(function);
         let closure = self.alloc(closure);
 
-        self.call(closure, 0)?;
+        if let Err(err) = self.call(closure, 0) {
+            self.report_error();
+            return Err(err);
+        }
 
-        self.run()
+        let result = self.run(0);
+        if result.is_err() {
+            self.report_error();
+            self.reset_stack();
+        }
+        self.interrupted.store(false, AtomicOrdering::Relaxed);
+        result
     }
 
     // Returning an error from this function (including ?) halts execution
-    fn run(&mut self) -> Result<()> {
+    // Returns once the current fiber is back down to `depth` call frames
+    fn run(&mut self, depth: usize) -> Result<()> {
         loop {
             #[cfg(feature = "debug_trace_execution")]
             {
                 print!("        ");
-                println!("{:?}", self.stack);
+                println!("{:?}", self.fiber.stack);
                 let frame = self.current_frame();
                 crate::disassembler::disassemble_instruction_ptr(
                     &frame.closure.function.chunk,
@@ -88,20 +170,17 @@ impl Vm {
 
             match instruction {
                 OpCode::Add => {
-                    let b = *self.stack.peek(0);
-                    let a = *self.stack.peek(1);
+                    let b = *self.fiber.stack.peek(0);
+                    let a = *self.fiber.stack.peek(1);
                     match (a, b) {
-                        (Value::Number(a), Value::Number(b)) => {
-                            self.stack.pop();
-                            self.stack.pop();
-                            let result = Value::Number(a + b);
-                            self.stack.push(result);
+                        (Value::Number(_) | Value::Int(_), Value::Number(_) | Value::Int(_)) => {
+                            self.arithmetic_op(i64::checked_add, |a, b| a + b)?
                         }
                         (Value::String(a), Value::String(b)) => {
-                            self.stack.pop();
-                            self.stack.pop();
                             let result = self.intern(format!("{}{}", a.as_str(), b.as_str()));
-                            self.stack.push(Value::String(result));
+                            self.fiber.stack.pop();
+                            self.fiber.stack.pop();
+                            self.fiber.stack.push(Value::String(result));
                         }
                         _ => {
                             return self
@@ -111,57 +190,135 @@ impl Vm {
                 }
                 OpCode::Constant(constant) => {
                     let constant = self.current_frame().read_constant(constant);
-                    self.stack.push(constant);
+                    self.fiber.stack.push(constant);
                 }
-                OpCode::Divide => self.binary_op(|a, b| Value::Number(a / b))?,
-                OpCode::Multiply => self.binary_op(|a, b| Value::Number(a * b))?,
-                OpCode::Negate => {
-                    if let Value::Number(value) = *self.stack.peek(0) {
-                        self.stack.pop();
-                        self.stack.push(Value::Number(-value));
+                OpCode::Divide => self.float_op(|a, b| a / b)?,
+                OpCode::IntDivide => {
+                    self.check_int_divisor()?;
+                    self.arithmetic_op(i64::checked_div, |a, b| (a / b).trunc())?
+                }
+                OpCode::Modulo => {
+                    self.check_int_divisor()?;
+                    self.arithmetic_op(i64::checked_rem, |a, b| a % b)?
+                }
+                OpCode::Multiply => self.arithmetic_op(i64::checked_mul, |a, b| a * b)?,
+                OpCode::Power => {
+                    // A negative integer exponent gives a fraction, so it's computed with floats
+                    if let (Value::Int(a), Value::Int(b)) =
+                        (*self.fiber.stack.peek(1), *self.fiber.stack.peek(0))
+                    {
+                        if b < 0 {
+                            self.fiber.stack.pop();
+                            self.fiber.stack.pop();
+                            self.fiber
+                                .stack
+                                .push(Value::Number((a as f64).powf(b as f64)));
+                            continue;
+                        }
+                    }
+                    self.arithmetic_op(
+                        |a, b| a.checked_pow(u32::try_from(b).ok()?),
+                        |a, b| a.powf(b),
+                    )?
+                }
+                OpCode::BitAnd => self.bitwise_op(|a, b| Some(a & b))?,
+                OpCode::BitOr => self.bitwise_op(|a, b| Some(a | b))?,
+                OpCode::BitXor => self.bitwise_op(|a, b| Some(a ^ b))?,
+                OpCode::BitNot => {
+                    if let Value::Int(value) = *self.fiber.stack.peek(0) {
+                        self.fiber.stack.pop();
+                        self.fiber.stack.push(Value::Int(!value));
                     } else {
-                        return self.runtime_error("Operand must be a number.");
+                        return self.runtime_error("Operand must be an integer.");
                     }
                 }
+                OpCode::ShiftLeft => {
+                    self.bitwise_op(|a, b| a.checked_shl(u32::try_from(b).ok()?))?
+                }
+                OpCode::ShiftRight => {
+                    self.bitwise_op(|a, b| a.checked_shr(u32::try_from(b).ok()?))?
+                }
+                OpCode::Negate => {
+                    let result = match *self.fiber.stack.peek(0) {
+                        Value::Number(value) => Value::Number(-value),
+                        Value::Int(value) => match value.checked_neg() {
+                            Some(value) => Value::Int(value),
+                            None => return self.runtime_error("Integer overflow."),
+                        },
+                        _ => return self.runtime_error("Operand must be a number."),
+                    };
+                    self.fiber.stack.pop();
+                    self.fiber.stack.push(result);
+                }
                 OpCode::Return => {
-                    let result = self.stack.pop();
-                    let fun_stack_start = self.frames.pop().slot;
+                    let result = self.fiber.stack.pop();
+                    let fun_stack_start = self.fiber.frames.pop().slot;
                     self.close_upvalues(fun_stack_start);
-                    if self.frames.len() == 0 {
-                        // Exit interpreter
-                        return Ok(());
+                    if self.fiber.is_done() {
+                        match self.fiber.caller.take() {
+                            // Hand the fiber's result back to whichever fiber resumed it
+                            Some(caller) => {
+                                self.fiber.stack.truncate(fun_stack_start);
+                                self.fiber = caller;
+                                self.fiber.stack.push(result);
+                            }
+                            // Exit interpreter
+                            None => return Ok(()),
+                        }
+                    } else {
+                        self.fiber.stack.truncate(fun_stack_start);
+                        self.fiber.stack.push(result);
+                        if self.fiber.frames.len() == depth {
+                            return Ok(());
+                        }
                     }
-                    self.stack.truncate(fun_stack_start);
-                    self.stack.push(result);
                 }
-                OpCode::Subtract => self.binary_op(|a, b| Value::Number(a - b))?,
-                OpCode::Nil => self.stack.push(Value::Nil),
-                OpCode::True => self.stack.push(Value::Bool(true)),
-                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Subtract => self.arithmetic_op(i64::checked_sub, |a, b| a - b)?,
+                OpCode::Nil => self.fiber.stack.push(Value::Nil),
+                OpCode::True => self.fiber.stack.push(Value::Bool(true)),
+                OpCode::False => self.fiber.stack.push(Value::Bool(false)),
                 OpCode::Not => {
-                    let value = self.stack.pop();
-                    self.stack.push(Value::Bool(value.is_falsey()));
+                    let value = self.fiber.stack.pop();
+                    self.fiber.stack.push(Value::Bool(value.is_falsey()));
                 }
                 OpCode::Equal => {
-                    let a = self.stack.pop();
-                    let b = self.stack.pop();
-                    self.stack.push(Value::Bool(a == b))
+                    let a = self.fiber.stack.pop();
+                    let b = self.fiber.stack.pop();
+                    self.fiber.stack.push(Value::Bool(a == b))
                 }
-                OpCode::Greater => self.binary_op(|a, b| Value::Bool(a > b))?,
-                OpCode::Less => self.binary_op(|a, b| Value::Bool(a < b))?,
-                OpCode::Print => println!("{}", self.stack.pop()),
+                OpCode::Greater => self.comparison_op(Ordering::is_gt)?,
+                OpCode::Less => self.comparison_op(Ordering::is_lt)?,
+                OpCode::Print => println!("{}", self.fiber.stack.pop()),
                 OpCode::Pop => {
-                    self.stack.pop();
+                    self.fiber.stack.pop();
+                }
+                OpCode::Dup => {
+                    let value = *self.fiber.stack.peek(0);
+                    self.fiber.stack.push(value);
+                }
+                OpCode::DupPair => {
+                    let first = *self.fiber.stack.peek(1);
+                    let second = *self.fiber.stack.peek(0);
+                    self.fiber.stack.push(first);
+                    self.fiber.stack.push(second);
                 }
                 OpCode::DefineGlobal(constant) => {
                     let name = self.read_string(constant);
-                    self.globals.insert(name, *self.stack.peek(0));
-                    self.stack.pop();
+                    self.check_not_const_global(name)?;
+                    self.globals.insert(name, *self.fiber.stack.peek(0));
+                    self.fiber.stack.pop();
+                }
+                OpCode::DefineConstGlobal(constant) => {
+                    let name = self.read_string(constant);
+                    self.check_not_const_global(name)?;
+                    self.globals.insert(name, *self.fiber.stack.peek(0));
+                    self.const_globals.insert(name, Value::Nil);
+                    self.fiber.stack.pop();
                 }
                 OpCode::GetGlobal(constant) => {
                     let name = self.read_string(constant);
                     if let Some(value) = self.globals.get(name) {
-                        self.stack.push(value);
+                        self.fiber.stack.push(value);
                     } else {
                         return self
                             .runtime_error(&format!("Undefined variable '{}'.", name.as_str()));
@@ -169,7 +326,13 @@ impl Vm {
                 }
                 OpCode::SetGlobal(constant) => {
                     let name = self.read_string(constant);
-                    if self.globals.insert(name, *self.stack.peek(0)) {
+                    if self.const_globals.get(name).is_some() {
+                        return self.runtime_error(&format!(
+                            "Cannot assign to constant '{}'.",
+                            name.as_str()
+                        ));
+                    }
+                    if self.globals.insert(name, *self.fiber.stack.peek(0)) {
                         self.globals.remove(name);
                         return self
                             .runtime_error(&format!("Undefined variable '{}'.", name.as_str()));
@@ -177,14 +340,26 @@ impl Vm {
                 }
                 OpCode::GetLocal(offset) => {
                     let offset = self.current_frame().read_local_offset(offset);
-                    self.stack.push(*self.stack.read(offset));
+                    let value = *self.fiber.stack.read(offset);
+                    self.fiber.stack.push(value);
                 }
                 OpCode::SetLocal(offset) => {
                     let offset = self.current_frame().read_local_offset(offset);
-                    self.stack.write(offset, *self.stack.peek(0));
+                    let value = *self.fiber.stack.peek(0);
+                    self.fiber.stack.write(offset, value);
                 }
                 OpCode::JumpIfFalse(jump) => {
-                    if self.stack.peek(0).is_falsey() {
+                    if self.fiber.stack.peek(0).is_falsey() {
+                        self.current_frame().jump(jump);
+                    }
+                }
+                OpCode::JumpIfNil(jump) => {
+                    if let Value::Nil = self.fiber.stack.peek(0) {
+                        self.current_frame().jump(jump);
+                    }
+                }
+                OpCode::JumpIfNotNil(jump) => {
+                    if !matches!(self.fiber.stack.peek(0), Value::Nil) {
                         self.current_frame().jump(jump);
                     }
                 }
@@ -193,12 +368,13 @@ impl Vm {
                     frame.jump(jump);
                 }
                 OpCode::Loop(jump) => {
+                    self.check_interrupt()?;
                     let frame = self.current_frame();
                     frame.jump_backwards(jump);
                 }
                 OpCode::Call { arg_count } => {
                     let arg_count = arg_count as usize;
-                    self.call_value(*self.stack.peek(arg_count), arg_count)?;
+                    self.call_value(*self.fiber.stack.peek(arg_count), arg_count)?;
                 }
                 OpCode::Closure(constant) => {
                     // Load the compiled function from the constant table
@@ -206,6 +382,8 @@ impl Vm {
                     if let Value::Function(function) = function {
                         // Wrap that function in a new closure object and push it onto the stack
                         let mut closure = Closure::new(function);
+                        // Functions nested in a trait method share its superclass
+                        closure.home_class = self.current_frame().closure.home_class;
 
                         // Iterate over each upvalue the closure expects
                         for FunctionUpvalue { is_local, index } in function.upvalues.iter() {
@@ -221,98 +399,404 @@ impl Vm {
                             closure.upvalues.push(upvalue);
                         }
                         let closure = self.alloc(closure);
-                        self.stack.push(Value::Closure(closure));
+                        self.fiber.stack.push(Value::Closure(closure));
                     } else {
                         unreachable!()
                     }
                 }
                 OpCode::GetUpvalue(index) => {
                     let upvalue = self.current_frame().closure.upvalues[index as usize];
-                    let value = upvalue.read(&self.stack);
-                    self.stack.push(value);
+                    let value = upvalue.read();
+                    self.fiber.stack.push(value);
                 }
                 OpCode::SetUpvalue(index) => {
                     let mut upvalue = self.current_frame().closure.upvalues[index as usize];
-                    upvalue.write(&mut self.stack);
+                    upvalue.write(*self.fiber.stack.peek(0));
                 }
                 OpCode::CloseUpvalue => {
-                    self.close_upvalues(self.stack.get_offset());
-                    self.stack.pop();
+                    self.close_upvalues(self.fiber.stack.get_offset());
+                    self.fiber.stack.pop();
+                }
+                OpCode::List { item_count } => {
+                    let item_count = item_count as usize;
+                    // The items stay on the stack until the list is allocated so the GC can still reach them
+                    let items = self.fiber.stack.peek_n(item_count).to_vec();
+                    let list = self.alloc(List::new(items));
+                    let length = self.fiber.stack.len() - item_count;
+                    self.fiber.stack.truncate(length);
+                    self.fiber.stack.push(Value::List(list));
+                }
+                OpCode::Unpack { item_count } => {
+                    let item_count = item_count as usize;
+                    let list = match self.fiber.stack.pop() {
+                        Value::List(list) => list,
+                        _ => return self.runtime_error("Can only destructure lists."),
+                    };
+                    if list.items.len() != item_count {
+                        return self.runtime_error(&format!(
+                            "Expected {} values to destructure but got {}.",
+                            item_count,
+                            list.items.len()
+                        ));
+                    }
+                    for item in list.items.iter() {
+                        self.fiber.stack.push(*item);
+                    }
+                }
+                OpCode::GetIndex => {
+                    let list = *self.fiber.stack.peek(1);
+                    let index = *self.fiber.stack.peek(0);
+                    let (list, index) = self.list_index(list, index)?;
+                    let item = list.items[index];
+                    self.fiber.stack.pop();
+                    self.fiber.stack.pop();
+                    self.fiber.stack.push(item);
+                }
+                OpCode::SetIndex => {
+                    let list = *self.fiber.stack.peek(2);
+                    let index = *self.fiber.stack.peek(1);
+                    let (mut list, index) = self.list_index(list, index)?;
+                    let value = self.fiber.stack.pop();
+                    list.items[index] = value;
+                    self.fiber.stack.pop();
+                    self.fiber.stack.pop();
+                    self.fiber.stack.push(value);
                 }
                 OpCode::Class(constant) => {
                     let name = self.read_string(constant);
                     let class = self.alloc(Class::new(name));
-                    self.stack.push(Value::Class(class));
+                    self.fiber.stack.push(Value::Class(class));
+                }
+                OpCode::Trait(constant) => {
+                    let name = self.read_string(constant);
+                    let mut class = Class::new(name);
+                    class.is_trait = true;
+                    let class = self.alloc(class);
+                    self.fiber.stack.push(Value::Class(class));
+                }
+                OpCode::Enum(constant) => {
+                    let name = self.read_string(constant);
+                    let mut class = Class::new(name);
+                    class.is_enum = true;
+                    let mut class = self.alloc(class);
+                    self.fiber.stack.push(Value::Class(class));
+                    let (name, values) = self.new_native("values", 0, Vm::enum_values);
+                    class.statics.insert(name, values);
+                }
+                OpCode::Variant { name, field_count } => {
+                    let name = self.read_string(name);
+                    let field_count = field_count as usize;
+                    let mut class = match *self.fiber.stack.peek(field_count * 2) {
+                        Value::Class(class) => class,
+                        _ => unreachable!(),
+                    };
+                    let mut variant = self.alloc(Instance::new(class));
+                    let ordinal = Value::Int(class.variants.len() as i64);
+                    let (name_string, ordinal_string) = (self.name_string, self.ordinal_string);
+                    variant.fields.insert(name_string, Value::String(name));
+                    variant.fields.insert(ordinal_string, ordinal);
+                    for pair in self.fiber.stack.peek_n(field_count * 2).chunks(2) {
+                        match pair[0] {
+                            Value::String(field) => variant.fields.insert(field, pair[1]),
+                            _ => unreachable!(),
+                        };
+                    }
+                    let len = self.fiber.stack.len() - field_count * 2;
+                    self.fiber.stack.truncate(len);
+                    class.statics.insert(name, Value::Instance(variant));
+                    class.variants.push((name, variant));
+                }
+                OpCode::Include { trait_count } => {
+                    let trait_count = trait_count as usize;
+                    let class = match *self.fiber.stack.peek(trait_count) {
+                        Value::Class(class) => class,
+                        _ => unreachable!(),
+                    };
+                    let mut traits = Vec::with_capacity(trait_count);
+                    for value in self.fiber.stack.peek_n(trait_count) {
+                        match value {
+                            Value::Class(t) if t.is_trait => traits.push(*t),
+                            _ => return self.runtime_error("Can only include traits."),
+                        }
+                    }
+
+                    self.include_traits(class, &traits)?;
+                    let length = self.fiber.stack.len() - trait_count;
+                    self.fiber.stack.truncate(length);
                 }
                 OpCode::GetProperty(constant) => {
-                    let instance = match *self.stack.peek(0) {
+                    let instance = match *self.fiber.stack.peek(0) {
                         Value::Instance(instance) => instance,
+                        Value::Class(class) => {
+                            let name = self.read_string(constant);
+                            self.bind_static(class, name)?;
+                            continue;
+                        }
                         _ => return self.runtime_error("Only instances have properties."),
                     };
                     let name = self.read_string(constant);
                     if let Some(value) = instance.fields.get(name) {
-                        self.stack.pop(); // Instance
-                        self.stack.push(value);
+                        self.fiber.stack.pop(); // Instance
+                        self.fiber.stack.push(value);
+                    } else if let Some(Value::Closure(getter)) = instance.class.getters.get(name) {
+                        // The instance on top of the stack becomes the getter's 'this'
+                        self.call(getter, 0)?;
                     } else {
                         self.bind_method(instance.class, name)?;
                     }
                 }
                 OpCode::SetProperty(constant) => {
-                    let instance = *self.stack.peek(1);
-                    let mut instance = match instance {
-                        Value::Instance(instance) => instance,
+                    let instance = *self.fiber.stack.peek(1);
+                    let name = self.read_string(constant);
+                    let value = *self.fiber.stack.peek(0);
+                    match instance {
+                        Value::Instance(instance) if instance.class.is_enum => {
+                            return self.runtime_error("Cannot modify an enum variant.");
+                        }
+                        Value::Class(class) if class.is_enum => {
+                            return self.runtime_error("Cannot modify an enum.");
+                        }
+                        Value::Instance(instance) => {
+                            if let Some(Value::Closure(setter)) = instance.class.setters.get(name) {
+                                self.call(setter, 1)?;
+                                continue;
+                            }
+                            if instance.class.getters.get(name).is_some() {
+                                return self.runtime_error(&format!(
+                                    "Property '{}' has a getter but no setter.",
+                                    name.as_str()
+                                ));
+                            }
+                            let mut instance = instance;
+                            instance.fields.insert(name, value)
+                        }
+                        Value::Class(mut class) => class.statics.insert(name, value),
                         _ => return self.runtime_error("Only instances have fields."),
                     };
-                    let name = self.read_string(constant);
-                    let value = *self.stack.peek(0);
-                    instance.fields.insert(name, value);
 
                     // Remove 2nd element from the stack (the instance)
-                    let value = self.stack.pop();
-                    self.stack.pop();
-                    self.stack.push(value);
+                    let value = self.fiber.stack.pop();
+                    self.fiber.stack.pop();
+                    self.fiber.stack.push(value);
                 }
                 OpCode::Method(constant) => {
                     let name = self.read_string(constant);
                     self.define_method(name);
                 }
+                OpCode::Getter(constant) => {
+                    let name = self.read_string(constant);
+                    let getter = self.fiber.stack.pop();
+                    let mut class = match self.fiber.stack.peek(0) {
+                        Value::Class(class) => *class,
+                        _ => unreachable!(),
+                    };
+                    class.getters.insert(name, getter);
+                }
+                OpCode::Setter(constant) => {
+                    let name = self.read_string(constant);
+                    let setter = self.fiber.stack.pop();
+                    let mut class = match self.fiber.stack.peek(0) {
+                        Value::Class(class) => *class,
+                        _ => unreachable!(),
+                    };
+                    class.setters.insert(name, setter);
+                }
+                OpCode::PrivateMethod(constant) => {
+                    let name = self.read_string(constant);
+                    let method = self.fiber.stack.pop();
+                    let mut class = match self.fiber.stack.peek(0) {
+                        Value::Class(class) => *class,
+                        _ => unreachable!(),
+                    };
+                    class.private_methods.insert(name, method);
+                }
+                OpCode::Static(constant) => {
+                    let name = self.read_string(constant);
+                    let value = self.fiber.stack.pop();
+                    let mut class = match self.fiber.stack.peek(0) {
+                        Value::Class(class) => *class,
+                        _ => unreachable!(),
+                    };
+                    class.statics.insert(name, value);
+                }
                 OpCode::Invoke(Invoke { name, arg_count }) => {
                     let method = self.read_string(name);
                     self.invoke(method, arg_count as usize)?;
                 }
                 OpCode::Inherit => {
-                    let superclass = match self.stack.peek(1) {
-                        Value::Class(class) => class,
+                    let superclass = match *self.fiber.stack.peek(1) {
+                        Value::Class(class) if self.is_builtin_class(class) => {
+                            return self.runtime_error(&format!(
+                                "Cannot inherit from built-in class '{}'.",
+                                class.name.as_str()
+                            ));
+                        }
+                        Value::Class(class) if !class.is_trait && !class.is_enum => class,
                         _ => return self.runtime_error("Superclass must be a class."),
                     };
-                    match self.stack.peek(0) {
-                        Value::Class(mut subclass) => subclass.methods.append(&superclass.methods),
+                    // Pop the subclass, leaving the superclass as the 'super' local
+                    match self.fiber.stack.pop() {
+                        Value::Class(mut subclass) => {
+                            subclass.methods.append(&superclass.methods);
+                            subclass.getters.append(&superclass.getters);
+                            subclass.setters.append(&superclass.setters);
+                            // Statics are copied too, so each class has its own slot for an inherited static field
+                            subclass.statics.append(&superclass.statics);
+                            subclass.superclass = Some(superclass);
+                        }
+                        _ => unreachable!(),
+                    };
+                }
+                OpCode::IsInstance => {
+                    let class = match self.fiber.stack.pop() {
+                        Value::Class(class) => class,
+                        _ => return self.runtime_error("Operand must be a class."),
+                    };
+                    let value = self.fiber.stack.pop();
+                    let result = self
+                        .class_of(value)
+                        .is_some_and(|value_class| value_class.is_subclass_of(class));
+                    self.fiber.stack.push(Value::Bool(result));
+                }
+                OpCode::HasField(constant) => {
+                    let name = self.read_string(constant);
+                    let result = match self.fiber.stack.pop() {
+                        Value::Instance(instance) => instance.fields.get(name).is_some(),
+                        _ => false,
+                    };
+                    self.fiber.stack.push(Value::Bool(result));
+                }
+                OpCode::GetPrivate(constant) => {
+                    let name = self.read_string(constant);
+                    let class = self.pop_private_owner(0)?;
+                    let instance = match *self.fiber.stack.peek(0) {
+                        Value::Instance(instance) => instance,
+                        _ => unreachable!(),
+                    };
+
+                    if let Some(value) = instance.get_private(class, name) {
+                        self.fiber.stack.pop();
+                        self.fiber.stack.push(value);
+                    } else if let Some(Value::Closure(method)) = class.private_methods.get(name) {
+                        let bound = self.alloc(BoundMethod::new(
+                            Value::Instance(instance),
+                            Value::Closure(method),
+                        ));
+                        self.fiber.stack.pop();
+                        self.fiber.stack.push(Value::BoundMethod(bound));
+                    } else {
+                        return self
+                            .runtime_error(&format!("Undefined property '{}'.", name.as_str()));
+                    }
+                }
+                OpCode::SetPrivate(constant) => {
+                    let name = self.read_string(constant);
+                    let class = self.pop_private_owner(1)?;
+                    let value = self.fiber.stack.pop();
+                    match self.fiber.stack.pop() {
+                        Value::Instance(mut instance) => instance.set_private(class, name, value),
+                        _ => unreachable!(),
+                    }
+                    self.fiber.stack.push(value);
+                }
+                OpCode::InvokePrivate(Invoke { name, arg_count }) => {
+                    let name = self.read_string(name);
+                    let arg_count = arg_count as usize;
+                    let class = self.pop_private_owner(arg_count)?;
+                    let instance = match *self.fiber.stack.peek(arg_count) {
+                        Value::Instance(instance) => instance,
                         _ => unreachable!(),
                     };
+
+                    if let Some(value) = instance.get_private(class, name) {
+                        let slot = self.fiber.stack.get_offset() - arg_count - 1;
+                        self.fiber.stack.write(slot, value);
+                        self.call_value(value, arg_count)?;
+                    } else if let Some(method) = class.private_methods.get(name) {
+                        self.call_value(method, arg_count)?;
+                    } else {
+                        return self
+                            .runtime_error(&format!("Undefined property '{}'.", name.as_str()));
+                    }
+                }
+                OpCode::HomeSuperclass => {
+                    let home_class = self.current_frame().closure.home_class;
+                    match home_class.and_then(|class| class.superclass) {
+                        Some(superclass) => self.fiber.stack.push(Value::Class(superclass)),
+                        None => return self.runtime_error(
+                            "Cannot use 'super' in a trait included by a class with no superclass.",
+                        ),
+                    }
                 }
                 OpCode::GetSuper(constant) => {
                     let name = self.read_string(constant);
-                    let class = match self.stack.pop() {
+                    let class = match self.fiber.stack.pop() {
                         Value::Class(class) => class,
                         _ => unreachable!(),
                     };
 
-                    self.bind_method(class, name)?;
+                    if let Some(Value::Closure(getter)) = class.getters.get(name) {
+                        self.call(getter, 0)?;
+                    } else {
+                        self.bind_method(class, name)?;
+                    }
+                }
+                OpCode::SetSuper(constant) => {
+                    let name = self.read_string(constant);
+                    let class = match self.fiber.stack.pop() {
+                        Value::Class(class) => class,
+                        _ => unreachable!(),
+                    };
+                    let mut instance = match *self.fiber.stack.peek(1) {
+                        Value::Instance(instance) => instance,
+                        _ => unreachable!(),
+                    };
+
+                    if let Some(Value::Closure(setter)) = class.setters.get(name) {
+                        self.call(setter, 1)?;
+                    } else if class.getters.get(name).is_some() {
+                        return self.runtime_error(&format!(
+                            "Property '{}' has a getter but no setter.",
+                            name.as_str()
+                        ));
+                    } else {
+                        let value = self.fiber.stack.pop();
+                        instance.fields.insert(name, value);
+                        self.fiber.stack.pop();
+                        self.fiber.stack.push(value);
+                    }
                 }
                 OpCode::SuperInvoke(Invoke { name, arg_count }) => {
                     let method = self.read_string(name);
-                    let class = match self.stack.pop() {
+                    let class = match self.fiber.stack.pop() {
                         Value::Class(class) => class,
                         _ => unreachable!(),
                     };
                     self.invoke_from_class(class, method, arg_count as usize)?;
                 }
+                OpCode::GetSuperStatic(constant) => {
+                    let name = self.read_string(constant);
+                    let class = match self.fiber.stack.pop() {
+                        Value::Class(class) => class,
+                        _ => unreachable!(),
+                    };
+
+                    self.bind_static(class, name)?;
+                }
+                OpCode::SuperInvokeStatic(Invoke { name, arg_count }) => {
+                    let method = self.read_string(name);
+                    let class = match self.fiber.stack.pop() {
+                        Value::Class(class) => class,
+                        _ => unreachable!(),
+                    };
+                    self.invoke_static(class, method, arg_count as usize)?;
+                }
             }
         }
     }
 
     fn current_frame(&mut self) -> &mut CallFrame {
-        self.frames.top()
+        self.fiber.frames.top()
     }
 
     fn read_string(&mut self, constant: Constant) -> GcRef<LoxString> {
@@ -322,37 +806,195 @@ impl Vm {
         }
     }
 
-    fn binary_op(&mut self, f: impl Fn(f64, f64) -> Value) -> Result<()> {
-        let b = *self.stack.peek(0);
-        let a = *self.stack.peek(1);
-        match (a, b) {
-            (Value::Number(a), Value::Number(b)) => {
-                self.stack.pop();
-                self.stack.pop();
-                let result = f(a, b);
-                self.stack.push(result);
-                Ok(())
+    /// Copy the traits' methods, getters and setters into the class, unless the class defines them
+    /// itself. Two traits can't provide different versions of the same member.
+    fn include_traits(&mut self, mut class: GcRef<Class>, traits: &[GcRef<Class>]) -> Result<()> {
+        let members: [fn(&mut Class) -> &mut Table; 3] = [
+            |class| &mut class.methods,
+            |class| &mut class.getters,
+            |class| &mut class.setters,
+        ];
+
+        for member in members {
+            let mut provided: Vec<(GcRef<LoxString>, GcRef<Closure>, GcRef<Class>)> = Vec::new();
+            for mut t in traits.iter().copied() {
+                let entries: Vec<_> = member(&mut t).iter().collect();
+                for (name, value) in entries {
+                    let closure = match value {
+                        Value::Closure(closure) => closure,
+                        _ => unreachable!(),
+                    };
+                    if Self::defines_own_member(class, member, name) {
+                        continue;
+                    }
+                    match provided.iter().find(|(other_name, ..)| *other_name == name) {
+                        // Traits sharing a trait both provide its methods, which isn't a conflict
+                        Some((_, other, _)) if other.function == closure.function => {}
+                        Some((_, _, other_trait)) => {
+                            return self.runtime_error(&format!(
+                                "Traits {} and {} both define '{}', so {} must define it itself.",
+                                other_trait.name.as_str(),
+                                t.name.as_str(),
+                                name.as_str(),
+                                class.name.as_str()
+                            ))
+                        }
+                        None => provided.push((name, closure, t)),
+                    }
+                }
+            }
+
+            for (name, closure, _) in provided {
+                // Each class gets its own copy of the method, which knows where to find its 'super'
+                let mut copy = Closure::new(closure.function);
+                copy.upvalues = closure.upvalues.clone();
+                copy.home_class = Some(class);
+                let copy = self.alloc(copy);
+                member(&mut class).insert(name, Value::Closure(copy));
+            }
+        }
+
+        class.traits.extend_from_slice(traits);
+        Ok(())
+    }
+
+    /// Was the member declared in the class body, rather than inherited from its superclass?
+    fn defines_own_member(
+        mut class: GcRef<Class>,
+        member: fn(&mut Class) -> &mut Table,
+        name: GcRef<LoxString>,
+    ) -> bool {
+        match (member(&mut class).get(name), class.superclass) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(value), Some(mut superclass)) => member(&mut superclass).get(name) != Some(value),
+        }
+    }
+
+    /// Check a value can be indexed with the given index
+    fn list_index(&self, list: Value, index: Value) -> Result<(GcRef<List>, usize)> {
+        let list = match list {
+            Value::List(list) => list,
+            _ => return self.runtime_error("Only lists can be indexed."),
+        };
+        match index {
+            Value::Int(index) if index >= 0 && (index as usize) < list.items.len() => {
+                Ok((list, index as usize))
             }
-            _ => self.runtime_error("Operands must be numbers."),
+            Value::Int(_) => self.runtime_error("List index out of range."),
+            _ => self.runtime_error("List index must be an integer."),
         }
     }
 
+    /// Classes whose objects aren't `Instance`s. Calling or inheriting from them would create
+    /// instances their natives can't handle.
+    fn is_builtin_class(&self, class: GcRef<Class>) -> bool {
+        class == self.fiber_class || class == self.list_class
+    }
+
+    /// The class a value is an instance of, including the classes of built-in objects
+    pub fn class_of(&self, value: Value) -> Option<GcRef<Class>> {
+        match value {
+            Value::Instance(instance) => Some(instance.class),
+            Value::Fiber(_) => Some(self.fiber_class),
+            Value::List(_) => Some(self.list_class),
+            Value::Host(host) => Some(host.class),
+            _ => None,
+        }
+    }
+
+    /// Two ints give an int, failing on overflow. Otherwise both operands are promoted to floats.
+    fn arithmetic_op(
+        &mut self,
+        int_op: impl Fn(i64, i64) -> Option<i64>,
+        float_op: impl Fn(f64, f64) -> f64,
+    ) -> Result<()> {
+        let b = *self.fiber.stack.peek(0);
+        let a = *self.fiber.stack.peek(1);
+        let (a, b) = match (a, b) {
+            (Value::Int(a), Value::Int(b)) => (a, b),
+            _ => return self.float_op(float_op),
+        };
+        let result = match int_op(a, b) {
+            Some(result) => result,
+            None => return self.runtime_error("Integer overflow."),
+        };
+        self.fiber.stack.pop();
+        self.fiber.stack.pop();
+        self.fiber.stack.push(Value::Int(result));
+        Ok(())
+    }
+
+    /// Both operands are promoted to floats, even two ints
+    fn float_op(&mut self, f: impl Fn(f64, f64) -> f64) -> Result<()> {
+        let b = *self.fiber.stack.peek(0);
+        let a = *self.fiber.stack.peek(1);
+        let result = match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => f(a, b),
+            _ => return self.runtime_error("Operands must be numbers."),
+        };
+        self.fiber.stack.pop();
+        self.fiber.stack.pop();
+        self.fiber.stack.push(Value::Number(result));
+        Ok(())
+    }
+
+    fn bitwise_op(&mut self, f: impl Fn(i64, i64) -> Option<i64>) -> Result<()> {
+        let b = *self.fiber.stack.peek(0);
+        let a = *self.fiber.stack.peek(1);
+        let result = match (a, b) {
+            (Value::Int(a), Value::Int(b)) => match f(a, b) {
+                Some(result) => result,
+                None => return self.runtime_error("Shift amount must be between 0 and 63."),
+            },
+            _ => return self.runtime_error("Operands must be integers."),
+        };
+        self.fiber.stack.pop();
+        self.fiber.stack.pop();
+        self.fiber.stack.push(Value::Int(result));
+        Ok(())
+    }
+
+    /// Integer division and modulo by zero are errors, unlike their float counterparts
+    fn check_int_divisor(&self) -> Result<()> {
+        match (self.fiber.stack.peek(1), self.fiber.stack.peek(0)) {
+            (Value::Int(_), Value::Int(0)) => self.runtime_error("Division by zero."),
+            _ => Ok(()),
+        }
+    }
+
+    fn comparison_op(&mut self, f: impl Fn(Ordering) -> bool) -> Result<()> {
+        let b = *self.fiber.stack.peek(0);
+        let a = *self.fiber.stack.peek(1);
+        if a.as_f64().is_none() || b.as_f64().is_none() {
+            return self.runtime_error("Operands must be numbers.");
+        }
+        // NaN compares false with everything
+        let result = a.compare_numbers(&b).is_some_and(f);
+        self.fiber.stack.pop();
+        self.fiber.stack.pop();
+        self.fiber.stack.push(Value::Bool(result));
+        Ok(())
+    }
+
     fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<()> {
         match callee {
-            Value::NativeFunction(callee) => {
-                let args = self.stack.pop_n(arg_count);
-                let result = (callee.function)(args);
-                self.stack.pop();
-                self.stack.push(result);
-                Ok(())
-            }
+            Value::NativeFunction(callee) => self.call_native(callee, arg_count),
             Value::Closure(callee) => self.call(callee, arg_count),
+            Value::Class(class) if class.is_trait => {
+                self.runtime_error("Cannot instantiate a trait.")
+            }
+            Value::Class(class) if class.is_enum => {
+                self.runtime_error("Cannot instantiate an enum.")
+            }
+            Value::Class(class) if self.is_builtin_class(class) => self.runtime_error(&format!(
+                "Cannot instantiate built-in class '{}'.",
+                class.name.as_str()
+            )),
             Value::Class(class) => {
                 let instance = self.alloc(Instance::new(class));
-                self.stack.write(
-                    self.stack.get_offset() - arg_count,
-                    Value::Instance(instance),
-                );
+                let slot = self.fiber.stack.get_offset() - arg_count;
+                self.fiber.stack.write(slot, Value::Instance(instance));
                 if let Some(initializer) = class.methods.get(self.init_string) {
                     match initializer {
                         Value::Closure(initializer) => return self.call(initializer, arg_count),
@@ -365,9 +1007,9 @@ impl Vm {
                 Ok(())
             }
             Value::BoundMethod(bound) => {
-                self.stack
-                    .write(self.stack.get_offset() - arg_count, bound.receiver);
-                self.call(bound.method, arg_count)
+                let slot = self.fiber.stack.get_offset() - arg_count;
+                self.fiber.stack.write(slot, bound.receiver);
+                self.call_value(bound.method, arg_count)
             }
 
             _ => self.runtime_error("Can only call functions and classes."),
@@ -381,70 +1023,222 @@ impl Vm {
         arg_count: usize,
     ) -> Result<()> {
         if let Some(method) = class.methods.get(name) {
-            match method {
-                Value::Closure(closure) => self.call(closure, arg_count),
-                _ => unreachable!(),
-            }
+            self.call_value(method, arg_count)
+        } else if let Some(getter) = class.getters.get(name) {
+            // Read the property first, then call whatever the getter returned
+            let slot = self.fiber.stack.get_offset() - arg_count - 1;
+            let receiver = *self.fiber.stack.peek(arg_count);
+            let bound = self.alloc(BoundMethod::new(receiver, getter));
+            let value = self.call_function(Value::BoundMethod(bound), &[])?;
+            self.fiber.stack.write(slot, value);
+            self.call_value(value, arg_count)
         } else {
             self.runtime_error(&format!("Undefined property '{}'.", name.as_str()))
         }
     }
 
+    /// Call a value from Rust and run it to completion, returning its result
+    pub fn call_function(&mut self, callee: Value, args: &[Value]) -> Result<Value> {
+        self.call_method(callee, callee, args)
+    }
+
+    /// Like `call_function`, but the method runs with the receiver as 'this'
+    pub fn call_method(&mut self, receiver: Value, callee: Value, args: &[Value]) -> Result<Value> {
+        let depth = self.fiber.frames.len();
+        self.fiber.stack.push(receiver);
+        for arg in args {
+            self.fiber.stack.push(*arg);
+        }
+        self.call_value(callee, args.len())?;
+
+        // Natives have already finished, but closures have only had their frame pushed
+        if self.fiber.frames.len() > depth {
+            self.nested_calls += 1;
+            let result = self.run(depth);
+            self.nested_calls -= 1;
+            result?;
+        }
+        Ok(self.fiber.stack.pop())
+    }
+
+    /// Like `call_function`, but a runtime error is caught and returned as its message, leaving
+    /// the VM as it was before the call
+    pub fn try_call_function(
+        &mut self,
+        callee: Value,
+        args: &[Value],
+    ) -> Result<std::result::Result<Value, String>> {
+        let depth = self.fiber.frames.len();
+        let stack_len = self.fiber.stack.len();
+        match self.call_function(callee, args) {
+            Ok(value) => Ok(Ok(value)),
+            Err(LoxError::RuntimeError) => {
+                self.close_upvalues(stack_len);
+                self.fiber.frames.truncate(depth);
+                self.fiber.stack.truncate(stack_len);
+                let (message, _) = self.pending_error.borrow_mut().take().unwrap();
+                Ok(Err(message))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     fn invoke(&mut self, name: GcRef<LoxString>, arg_count: usize) -> Result<()> {
-        let receiver = *self.stack.peek(arg_count);
+        let receiver = *self.fiber.stack.peek(arg_count);
         let receiver = match receiver {
             Value::Instance(instance) => instance,
+            Value::Class(class) => return self.invoke_static(class, name, arg_count),
+            Value::Fiber(_) => return self.invoke_from_class(self.fiber_class, name, arg_count),
+            Value::List(_) => return self.invoke_from_class(self.list_class, name, arg_count),
+            Value::Host(host) => return self.invoke_from_class(host.class, name, arg_count),
             _ => return self.runtime_error("Only instances have methods."),
         };
 
         if let Some(value) = receiver.fields.get(name) {
-            self.stack.write(self.stack.get_offset() - arg_count, value);
+            let slot = self.fiber.stack.get_offset() - arg_count;
+            self.fiber.stack.write(slot, value);
             return self.call_value(value, arg_count);
         }
 
         self.invoke_from_class(receiver.class, name, arg_count)
     }
 
+    fn invoke_static(
+        &mut self,
+        class: GcRef<Class>,
+        name: GcRef<LoxString>,
+        arg_count: usize,
+    ) -> Result<()> {
+        if let Some(method) = class.statics.get(name) {
+            self.call_value(method, arg_count)
+        } else {
+            self.runtime_error(&format!("Undefined property '{}'.", name.as_str()))
+        }
+    }
+
     fn bind_method(&mut self, class: GcRef<Class>, name: GcRef<LoxString>) -> Result<()> {
         let method = match class.methods.get(name) {
             Some(value) => value,
             None => return self.runtime_error(&format!("Undefined property '{}'.", name.as_str())),
         };
 
-        let closure = match method {
-            Value::Closure(closure) => closure,
+        let bound = self.alloc(BoundMethod::new(*self.fiber.stack.peek(0), method));
+        let bound = Value::BoundMethod(bound);
+
+        self.fiber.stack.pop();
+        self.fiber.stack.push(bound);
+
+        Ok(())
+    }
+
+    /// Pop the class which declared a private member, checking the receiver below it is one of its instances
+    fn pop_private_owner(&mut self, receiver_distance: usize) -> Result<GcRef<Class>> {
+        let class = match self.fiber.stack.pop() {
+            Value::Class(class) => class,
             _ => unreachable!(),
         };
+        match *self.fiber.stack.peek(receiver_distance) {
+            Value::Instance(instance) if instance.class.is_subclass_of(class) => Ok(class),
+            _ => self.runtime_error(&format!(
+                "Only instances of {} can access its private members.",
+                class.name.as_str()
+            )),
+        }
+    }
 
-        let bound = self.alloc(BoundMethod::new(*self.stack.peek(0), closure));
-        let bound = Value::BoundMethod(bound);
+    /// Replace the receiver on top of the stack with a static member of the class, binding static methods to the receiver
+    fn bind_static(&mut self, class: GcRef<Class>, name: GcRef<LoxString>) -> Result<()> {
+        let value = match class.statics.get(name) {
+            Some(method @ (Value::Closure(_) | Value::NativeFunction(_))) => {
+                let bound = self.alloc(BoundMethod::new(*self.fiber.stack.peek(0), method));
+                Value::BoundMethod(bound)
+            }
+            Some(value) => value,
+            None => return self.runtime_error(&format!("Undefined property '{}'.", name.as_str())),
+        };
+
+        self.fiber.stack.pop();
+        self.fiber.stack.push(value);
+
+        Ok(())
+    }
 
-        self.stack.pop();
-        self.stack.push(bound);
+    fn call_native(&mut self, native: GcRef<NativeFunction>, arg_count: usize) -> Result<()> {
+        if arg_count < native.arity || (arg_count > native.arity && !native.variadic) {
+            let max_arity = if native.variadic {
+                None
+            } else {
+                Some(native.arity)
+            };
+            return self.arity_error(native.arity, max_arity, arg_count);
+        }
 
+        // The arguments stay on the stack while the native runs so the GC can still reach them
+        let mut fiber = self.fiber;
+        let this = *fiber.stack.peek(arg_count);
+        let args = fiber.stack.peek_n(arg_count).to_vec();
+        let result = (native.function)(self, this, &args)?;
+
+        // The native may have switched fibers: clean up the caller's stack, but deliver the result to whichever fiber is now running
+        let slot = fiber.stack.get_offset() - arg_count;
+        fiber.stack.truncate(slot);
+        self.fiber.stack.push(result);
         Ok(())
     }
 
     fn call(&mut self, callee: GcRef<Closure>, arg_count: usize) -> Result<()> {
-        if arg_count != callee.function.arity {
-            return self.runtime_error(&format!(
-                "Expected {} arguments but got {}.",
-                callee.function.arity, arg_count
-            ));
+        let function = callee.function;
+        if arg_count < function.min_arity || (arg_count > function.arity && !function.variadic) {
+            let max_arity = if function.variadic {
+                None
+            } else {
+                Some(function.arity)
+            };
+            return self.arity_error(function.min_arity, max_arity, arg_count);
         }
 
-        if self.frames.len() == Self::FRAMES_MAX {
+        self.check_interrupt()?;
+        if self.fiber.frames.len() == Self::FRAMES_MAX {
             return self.runtime_error("Stack overflow.");
         }
 
-        let slot = self.stack.get_offset() - arg_count;
-        self.frames.push(CallFrame::new(callee, slot));
+        // Missing optional arguments start as nil, and their default values are filled in by
+        // the code skipped over when they are passed
+        for _ in arg_count..function.arity {
+            self.fiber.stack.push(Value::Nil);
+        }
+        if function.variadic {
+            let extra = arg_count.saturating_sub(function.arity);
+            let items = self.fiber.stack.peek_n(extra).to_vec();
+            // The extra arguments are still on the stack, so they survive this allocation
+            let rest = self.alloc(List::new(items));
+            let len = self.fiber.stack.len() - extra;
+            self.fiber.stack.truncate(len);
+            self.fiber.stack.push(Value::List(rest));
+        }
+
+        let slot = self.fiber.stack.get_offset() - (function.arity + function.variadic as usize);
+        self.fiber
+            .frames
+            .push(CallFrame::new(callee, slot, arg_count));
         Ok(())
     }
 
+    fn arity_error<T>(&self, min: usize, max: Option<usize>, arg_count: usize) -> Result<T> {
+        let expected = match max {
+            Some(max) if max == min => format!("{}", min),
+            Some(max) => format!("{} to {}", min, max),
+            None => format!("at least {}", min),
+        };
+        self.runtime_error(&format!(
+            "Expected {} arguments but got {}.",
+            expected, arg_count
+        ))
+    }
+
     fn capture_upvalue(&mut self, local: usize) -> GcRef<Upvalue> {
         let mut prev_upvalue = None;
-        let mut maybe_upvalue = self.open_upvalues;
+        let mut maybe_upvalue = self.fiber.open_upvalues;
         while let Some(upvalue) = maybe_upvalue {
             if upvalue.location <= local {
                 break;
@@ -460,62 +1254,356 @@ impl Vm {
             }
         }
 
-        let created_upvalue = Upvalue::new(local, maybe_upvalue);
+        let created_upvalue = Upvalue::new(self.fiber, local, maybe_upvalue);
         let created_upvalue = self.alloc(created_upvalue);
 
         // Insert new upvalue between 'prev_upvalue' and 'upvalue'
         if let Some(mut prev_upvalue) = prev_upvalue {
             prev_upvalue.next = Some(created_upvalue);
         } else {
-            self.open_upvalues = Some(created_upvalue);
+            self.fiber.open_upvalues = Some(created_upvalue);
         }
 
         created_upvalue
     }
 
     fn close_upvalues(&mut self, last: usize) {
-        while let Some(mut upvalue) = self.open_upvalues {
+        while let Some(mut upvalue) = self.fiber.open_upvalues {
             if upvalue.location < last {
                 break;
             }
-            upvalue.closed = Some(*self.stack.read(upvalue.location));
-            self.open_upvalues = upvalue.next;
+            upvalue.closed = Some(upvalue.read());
+            self.fiber.open_upvalues = upvalue.next;
         }
     }
 
+    fn check_not_const_global(&self, name: GcRef<LoxString>) -> Result<()> {
+        if self.const_globals.get(name).is_some() {
+            return self.runtime_error(&format!("Cannot redefine constant '{}'.", name.as_str()));
+        }
+        Ok(())
+    }
+
     fn define_method(&mut self, name: GcRef<LoxString>) {
-        let method = *self.stack.peek(0);
-        let mut class = match self.stack.peek(1) {
+        let method = *self.fiber.stack.peek(0);
+        let mut class = match self.fiber.stack.peek(1) {
             Value::Class(class) => *class,
             _ => unreachable!(),
         };
         class.methods.insert(name, method);
-        self.stack.pop();
+        self.fiber.stack.pop();
     }
 
-    fn runtime_error(&self, message: &str) -> Result<()> {
-        eprintln!("{}", message);
+    pub fn runtime_error<T>(&self, message: &str) -> Result<T> {
+        self.raise(message);
+        Err(LoxError::RuntimeError)
+    }
+
+    /// Raise a `PermissionDenied` error unless the operation is allowed, on the given path for
+    /// reads and writes. Unlike runtime errors, `try()` doesn't catch it.
+    pub fn check_permission(&self, permission: Permission, path: Option<&str>) -> Result<()> {
+        if self.permissions.allows(permission, path) {
+            return Ok(());
+        }
+        let message = match path {
+            Some(path) => format!("Permission denied: {} access to '{}'.", permission, path),
+            None => format!("Permission denied: {} access.", permission),
+        };
+        self.raise(&message);
+        Err(LoxError::PermissionDenied)
+    }
+
+    /// A flag which stops the running script with an `Interrupted` error once it's set, from any
+    /// thread. Loops, calls and natives that block all check it.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
+    /// Raise an `Interrupted` error if the script has been interrupted. Like permission errors,
+    /// `try()` doesn't catch it.
+    pub fn check_interrupt(&self) -> Result<()> {
+        if self.interrupted.load(AtomicOrdering::Relaxed) {
+            self.raise("Interrupted.");
+            return Err(LoxError::Interrupted);
+        }
+        Ok(())
+    }
 
-        // Print callstack
-        for i in (0..self.frames.len()).rev() {
-            let frame = self.frames.read(i);
+    fn raise(&self, message: &str) {
+        // Reported once it's clear nothing will catch it
+        let mut trace = String::new();
+        for i in (0..self.fiber.frames.len()).rev() {
+            let frame = self.fiber.frames.read(i);
             let closure = frame.closure;
             let instruction =
                 unsafe { frame.ip.offset_from(closure.function.chunk.code.as_ptr()) - 1 } as usize;
             let line = closure.function.chunk.lines[instruction];
-            eprintln!("[line {}] in {}", line, *closure);
+            trace.push_str(&format!("[line {}] in {}\n", line, *closure));
         }
+        *self.pending_error.borrow_mut() = Some((message.to_string(), trace));
+    }
 
-        Err(LoxError::RuntimeError)
+    /// Print the runtime error that stopped the script, with the callstack where it happened
+    fn report_error(&self) {
+        if let Some((message, trace)) = self.pending_error.borrow_mut().take() {
+            eprintln!("{}", message);
+            eprint!("{}", trace);
+        }
+    }
+
+    /// Discard every fiber's in-progress calls and return control to the root fiber
+    fn reset_stack(&mut self) {
+        self.fiber = self.root_fiber;
+        self.fiber.stack.truncate(0);
+        self.fiber.frames.truncate(0);
+        self.fiber.open_upvalues = None;
+        self.nested_calls = 0;
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let (name, native) = self.new_native(name, arity, function);
+        self.globals.insert(name, native);
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        // Keep the value reachable while the name is allocated
+        self.fiber.stack.push(value);
+        let name = self.intern(name.to_string());
+        self.fiber.stack.pop();
+        self.globals.insert(name, value);
+    }
+
+    /// Define a native taking at least `min_arity` arguments, which can check for any maximum itself
+    pub fn define_variadic_native(&mut self, name: &str, min_arity: usize, function: NativeFn) {
+        let (name, native) = self.new_variadic_native(name, min_arity, function);
+        self.globals.insert(name, native);
     }
 
-    fn define_native(&mut self, name: &str, function: NativeFn) {
+    /// Define a global class for natives to hang off, e.g. `File`
+    pub fn define_class(&mut self, name: &str) -> GcRef<Class> {
+        let name = self.intern(name.to_string());
+        self.fiber.stack.push(Value::String(name));
+        let class = self.alloc(Class::new(name));
+        self.fiber.stack.pop();
+        self.globals.insert(name, Value::Class(class));
+        class
+    }
+
+    /// Define a native callable on the class itself, e.g. `Fiber.new(fn)`
+    pub fn define_static_native(
+        &mut self,
+        mut class: GcRef<Class>,
+        name: &str,
+        arity: usize,
+        function: NativeFn,
+    ) {
+        let (name, native) = self.new_native(name, arity, function);
+        class.statics.insert(name, native);
+    }
+
+    /// Define a native callable on instances of the class, which receive the instance as `this`
+    pub fn define_method_native(
+        &mut self,
+        mut class: GcRef<Class>,
+        name: &str,
+        arity: usize,
+        function: NativeFn,
+    ) {
+        let (name, native) = self.new_native(name, arity, function);
+        class.methods.insert(name, native);
+    }
+
+    pub fn new_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: NativeFn,
+    ) -> (GcRef<LoxString>, Value) {
         let ls = self.intern(name.to_string());
         // Pushing and popping to and from stack is only to ensure no GC occurs on call to alloc
-        self.stack.push(Value::String(ls));
-        let native = self.alloc(NativeFunction::new(function));
-        self.globals.insert(ls, Value::NativeFunction(native));
-        self.stack.pop();
+        self.fiber.stack.push(Value::String(ls));
+        let native = self.alloc(NativeFunction::new(function, arity));
+        self.fiber.stack.pop();
+        (ls, Value::NativeFunction(native))
+    }
+
+    pub fn new_variadic_native(
+        &mut self,
+        name: &str,
+        min_arity: usize,
+        function: NativeFn,
+    ) -> (GcRef<LoxString>, Value) {
+        let (name, native) = self.new_native(name, min_arity, function);
+        if let Value::NativeFunction(mut native) = native {
+            native.variadic = true;
+        }
+        (name, native)
+    }
+
+    /// Report a call to a native with more arguments than it takes
+    pub fn check_max_arity(&self, max: usize, args: &[Value]) -> Result<()> {
+        if args.len() > max {
+            return self.arity_error(0, Some(max), args.len());
+        }
+        Ok(())
+    }
+
+    /// `try(fn, args...)` calls the function, returning `(true, result)`, or `(false, message)` if
+    /// it raises a runtime error
+    fn try_native(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+        let (ok, value) = match vm.try_call_function(args[0], &args[1..])? {
+            Ok(value) => (true, value),
+            Err(message) => (false, Value::String(vm.intern(message))),
+        };
+        vm.fiber.stack.push(value);
+        let result = vm.alloc(List::new(vec![Value::Bool(ok), value]));
+        vm.fiber.stack.pop();
+        Ok(Value::List(result))
+    }
+
+    fn fiber_new(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+        let closure = match args[0] {
+            Value::Closure(closure) if closure.function.variadic => {
+                return vm.runtime_error("Fiber function cannot take a rest parameter.")
+            }
+            Value::Closure(closure) if closure.function.arity <= 1 => closure,
+            Value::Closure(_) => {
+                return vm.runtime_error("Fiber function cannot take more than one parameter.")
+            }
+            _ => return vm.runtime_error("Argument must be a function."),
+        };
+
+        let mut fiber = vm.alloc(Fiber::new());
+        fiber.stack.push(args[0]);
+        // The value passed to the first resume() is pushed just after the closure. That slot is
+        // the parameter of a one-parameter function, or the reserved slot zero of one without any.
+        let slot = 1 - closure.function.arity;
+        let arity = closure.function.arity;
+        fiber.frames.push(CallFrame::new(closure, slot, arity));
+        Ok(Value::Fiber(fiber))
+    }
+
+    fn fiber_resume(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+        vm.check_max_arity(1, args)?;
+        let mut fiber = match this {
+            Value::Fiber(fiber) => fiber,
+            _ => return vm.runtime_error("Expected a fiber."),
+        };
+        if fiber.is_done() {
+            return vm.runtime_error("Cannot resume a finished fiber.");
+        }
+        if fiber == vm.fiber || fiber == vm.root_fiber || fiber.caller.is_some() {
+            return vm.runtime_error("Fiber has already been resumed.");
+        }
+        vm.check_can_switch_fibers()?;
+
+        // A fiber that hasn't started has nothing on its stack but its function. Whether the first
+        // resume() passes a value decides the entry point, so a parameter can take its default.
+        if fiber.stack.len() == 1 {
+            let CallFrame { closure, slot, .. } = fiber.frames.pop();
+            let arg_count = args.len().max(closure.function.min_arity);
+            fiber.frames.push(CallFrame::new(closure, slot, arg_count));
+        }
+
+        fiber.caller = Some(vm.fiber);
+        vm.fiber = fiber;
+        // Becomes the result of the fiber's pending Fiber.yield(), or its function's argument
+        Ok(args.first().copied().unwrap_or(Value::Nil))
+    }
+
+    fn fiber_yield(vm: &mut Vm, _this: Value, args: &[Value]) -> Result<Value> {
+        vm.check_max_arity(1, args)?;
+        vm.check_can_switch_fibers()?;
+        let caller = match vm.fiber.caller.take() {
+            Some(caller) => caller,
+            None => return vm.runtime_error("Cannot yield from the root fiber."),
+        };
+
+        vm.fiber = caller;
+        // Becomes the result of the caller's resume()
+        Ok(args.first().copied().unwrap_or(Value::Nil))
+    }
+
+    fn enum_values(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+        let class = match this {
+            Value::Class(class) => class,
+            _ => unreachable!(),
+        };
+        let variants = class
+            .variants
+            .iter()
+            .map(|(_, variant)| Value::Instance(*variant))
+            .collect();
+        Ok(Value::List(vm.alloc(List::new(variants))))
+    }
+
+    fn list_push(vm: &mut Vm, this: Value, args: &[Value]) -> Result<Value> {
+        match this {
+            Value::List(mut list) => list.items.push(args[0]),
+            _ => return vm.runtime_error("Expected a list."),
+        }
+        Ok(Value::Nil)
+    }
+
+    fn list_pop(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+        let mut list = match this {
+            Value::List(list) => list,
+            _ => return vm.runtime_error("Expected a list."),
+        };
+        match list.items.pop() {
+            Some(item) => Ok(item),
+            None => vm.runtime_error("Cannot pop from an empty list."),
+        }
+    }
+
+    fn list_len(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+        match this {
+            Value::List(list) => Ok(Value::Int(list.items.len() as i64)),
+            _ => vm.runtime_error("Expected a list."),
+        }
+    }
+
+    /// A nested call from Rust must return to the Rust code that made it, so it can't hand control to another fiber
+    fn check_can_switch_fibers(&self) -> Result<()> {
+        if self.nested_calls > 0 {
+            return self.runtime_error("Cannot switch fibers inside a nested call.");
+        }
+        Ok(())
+    }
+
+    fn fiber_is_done(vm: &mut Vm, this: Value, _args: &[Value]) -> Result<Value> {
+        match this {
+            Value::Fiber(fiber) => Ok(Value::Bool(fiber.is_done())),
+            _ => vm.runtime_error("Expected a fiber."),
+        }
+    }
+
+    /// Keep a value reachable by the garbage collector while a native allocates, until the
+    /// matching `pop_root`
+    pub fn push_root(&mut self, value: Value) {
+        self.fiber.stack.push(value);
+    }
+
+    pub fn pop_root(&mut self) -> Value {
+        self.fiber.stack.pop()
+    }
+
+    /// Wrap data owned by Rust in an object whose methods come from the given class
+    pub fn new_host(&mut self, class: GcRef<Class>, data: Box<dyn Any>) -> Value {
+        Value::Host(self.alloc(Host::new(class, data)))
+    }
+
+    /// A new list of new strings
+    pub fn new_string_list(&mut self, strings: Vec<String>) -> Value {
+        let mut list = self.alloc(List::new(Vec::with_capacity(strings.len())));
+        // Keep the list reachable while its strings are allocated
+        self.fiber.stack.push(Value::List(list));
+        for string in strings {
+            let string = self.intern(string);
+            list.items.push(Value::String(string));
+        }
+        self.fiber.stack.pop();
+        Value::List(list)
     }
 
     pub fn intern(&mut self, string: String) -> GcRef<LoxString> {
@@ -540,30 +1628,29 @@ impl Vm {
     }
 
     fn mark_roots(&mut self) {
-        // Stack
-        self.stack.mark_gray(&mut self.gc);
-
-        // Call frame closures
-        self.frames.mark_gray(&mut self.gc);
-
-        // Open upvalue list
-        let mut next = self.open_upvalues;
-        while let Some(upvalue) = next {
-            upvalue.read(&self.stack).mark_gray(&mut self.gc);
-            next = upvalue.next;
-        }
+        // Running fibers, which in turn mark their stacks, call frame closures, open upvalues and
+        // the fibers waiting on them
+        self.fiber.mark_gray(&mut self.gc);
+        self.root_fiber.mark_gray(&mut self.gc);
 
         // Globals
         self.globals.mark_gray(&mut self.gc);
+        self.const_globals.mark_gray(&mut self.gc);
+
+        self.init_string.mark_gray(&mut self.gc);
+        self.name_string.mark_gray(&mut self.gc);
+        self.ordinal_string.mark_gray(&mut self.gc);
+        self.fiber_class.mark_gray(&mut self.gc);
+        self.list_class.mark_gray(&mut self.gc);
     }
 }
 
 /// Represents a single ongoing function call
-struct CallFrame {
+pub struct CallFrame {
     closure: GcRef<Closure>,
     /// The instruction pointer of this function. Returning from this function will resume from here.
     ip: *const OpCode,
-    /// The first slot in the VM's value stack that this function can use
+    /// The first slot in the fiber's value stack that this function can use
     slot: usize,
 }
 
@@ -578,10 +1665,17 @@ impl Default for CallFrame {
 }
 
 impl CallFrame {
-    fn new(closure: GcRef<Closure>, slot: usize) -> Self {
+    fn new(closure: GcRef<Closure>, slot: usize, arg_count: usize) -> Self {
+        let function = closure.function;
         Self {
             closure,
-            ip: closure.function.chunk.code.as_ptr(),
+            ip: unsafe {
+                function
+                    .chunk
+                    .code
+                    .as_ptr()
+                    .add(function.entry_point(arg_count))
+            },
             slot,
         }
     }